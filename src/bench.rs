@@ -1,6 +1,7 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+use map::SplayMap;
 use quickcheck::{Arbitrary, StdGen};
 use rand;
 use rust_test::Bencher;
@@ -35,4 +36,17 @@ fn bench_128(bencher: &mut Bencher) { bench(128, bencher) }
 #[bench]
 fn bench_1024(bencher: &mut Bencher) { bench(1024, bencher) }
 
+fn bench_skewed_lookups(rebalance: bool, bencher: &mut Bencher) {
+    let mut map: SplayMap<i32, i32> = (0..1024).map(|key| (key, key)).collect();
+    if rebalance {
+        map.rebalance()
+    }
+    bencher.iter(|| {
+        (0..1024).filter_map(|key| map.get(&key)).sum::<i32>()
+    });
+}
 
+#[bench]
+fn bench_skewed_lookups_1024(bencher: &mut Bencher) { bench_skewed_lookups(false, bencher) }
+#[bench]
+fn bench_rebalanced_lookups_1024(bencher: &mut Bencher) { bench_skewed_lookups(true, bencher) }
@@ -0,0 +1,81 @@
+// Copyright 2016 The Servo Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Independent exclusion zones for the columns of a multi-column container.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use app_units::Au;
+use exclusions::{Exclusions, Placement, Side, Size};
+
+/// A set of columns of equal inline size, each with its own exclusions, that share a block flow.
+///
+/// Floats in one column never affect placement in another. Methods that take a column index
+/// panic if it is out of range.
+#[derive(Clone)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Columns {
+    columns: Vec<Exclusions>,
+}
+
+impl Columns {
+    /// Creates the given number of empty columns, each with the given inline size.
+    pub fn new(column_count: usize, column_inline_size: Au) -> Columns {
+        Columns {
+            columns: (0..column_count).map(|_| Exclusions::new(column_inline_size)).collect(),
+        }
+    }
+
+    /// Returns the number of columns.
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Returns the exclusions of the given column.
+    pub fn column(&self, column: usize) -> &Exclusions {
+        &self.columns[column]
+    }
+
+    /// Returns the exclusions of the given column mutably, for operations not forwarded here.
+    pub fn column_mut(&mut self, column: usize) -> &mut Exclusions {
+        &mut self.columns[column]
+    }
+
+    /// Places an object in the given column as `Exclusions::place` does.
+    pub fn place(&mut self, column: usize, alignment: Side, size: &Size) -> Placement {
+        self.columns[column].place(alignment, size)
+    }
+
+    /// Excludes an area in the given column as `Exclusions::exclude` does.
+    pub fn exclude(&mut self, column: usize, side: Side, size: &Size) {
+        self.columns[column].exclude(side, size)
+    }
+
+    /// Returns the height of the tallest column, or zero if there are no columns.
+    pub fn height(&self) -> Au {
+        self.balance().map_or(Au(0), |(_, height)| height)
+    }
+
+    /// Returns the index and height of the tallest column, or `None` if there are no columns.
+    ///
+    /// If several columns are equally tall, the first of them is returned. A balancing pass can
+    /// use this to decide which column's content to move.
+    pub fn balance(&self) -> Option<(usize, Au)> {
+        let mut tallest: Option<(usize, Au)> = None;
+        for (index, column) in self.columns.iter().enumerate() {
+            let height = column.height();
+            match tallest {
+                Some((_, tallest_height)) if tallest_height >= height => {}
+                Some(_) | None => tallest = Some((index, height)),
+            }
+        }
+        tallest
+    }
+}
@@ -12,173 +12,2387 @@
 //! 9.5.1.
 
 use app_units::Au;
-use map::SplayMap;
-use std::cmp::Ordering;
-use std::fmt::{self, Debug, Formatter};
-use std::i32;
-use std::iter;
+use map::{self, SplayMap};
+#[cfg(feature = "serde")]
+use serde::de::{self, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, Serializer};
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::cmp::{self, Ordering};
+use core::ops::Bound;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use core::fmt::Debug;
+use core::fmt::{self, Display, Formatter};
+use core::i32;
+use core::iter;
+use core::mem;
 
+/// The block end of the final, infinitely long band.
+///
+/// Excluded ranges are clipped to end at least one app unit above this, so that no finite band
+/// ever reaches it and the final band is always the only one that ends here.
 const MAX_AU: Au = Au(i32::MAX);
 
+/// A polygon excluded by `exclude_shape`: its side, origin, and vertices.
+type Shape = (Side, Point, Vec<(Au, Au)>);
+
+/// A rectangle excluded by `exclude_interval`: its block start and end and its inline start and
+/// end.
+type Interval = (Au, Au, Au, Au);
+
+/// An area excluded by `exclude_span`: its side, its inset, and its block start and end.
+type Span = (Side, Au, Au, Au);
+
 /// Tracks exclusions and allows objects to be placed adjacent to them.
 #[derive(Clone)]
 pub struct Exclusions {
     bands: SplayMap<Au, Band>,
     inline_size: Au,
+    /// The inline position of the physical left edge of the zone in the coordinate space of its
+    /// container, which is added to every inline position the zone returns.
+    inline_start: Au,
+    direction: Direction,
+    writing_mode: WritingMode,
+    /// The block size of the container, past which nothing fits, if it is limited.
+    max_block: Option<Au>,
+    /// The block positions set with `set_barrier` and not yet cleared, in the order they were
+    /// set.
+    barriers: Vec<Au>,
+    /// Every area passed to `exclude`, in order, so that the bands can be rebuilt.
+    excluded: Vec<(Side, Size)>,
+    /// Every block range passed to `exclude_full_width`, as `(block_start, block_size)`.
+    full_width: Vec<(Au, Au)>,
+    /// Every polygon passed to `exclude_shape`, with its side and origin.
+    shapes: Vec<Shape>,
+    /// Every rectangle passed to `exclude_interval`, as `(block_start, block_end, inline_start,
+    /// inline_end)` relative to the zone. These are kept apart from the bands, which only track
+    /// the exclusions touching either edge.
+    intervals: Vec<Interval>,
+    /// Every area passed to `exclude_span`, with the side it was excluded on.
+    spans: Vec<Span>,
+    /// The block position above which nothing may be placed, because a full-width exclusion or a
+    /// shape ends there or a span starts there. Above this position the bands no longer
+    /// necessarily narrow going down.
+    floor: Au,
+    /// How to undo the most recent call to `exclude`, if it can still be undone.
+    undo: Option<Undo>,
+    /// The lowest block position reached by any area excluded on the left.
+    left_bottom: Au,
+    /// The lowest block position reached by any area excluded on the right.
+    right_bottom: Au,
+}
+
+/// A saved copy of the state of an exclusion zone, which can be restored later.
+///
+/// The bands are stored compactly in a vector rather than as a tree.
+#[derive(Clone, Debug)]
+pub struct ExclusionsSnapshot {
+    bands: Vec<(Au, Band)>,
+    inline_size: Au,
+    inline_start: Au,
+    direction: Direction,
+    writing_mode: WritingMode,
+    max_block: Option<Au>,
+    excluded: Vec<(Side, Size)>,
+    full_width: Vec<(Au, Au)>,
+    shapes: Vec<Shape>,
+    intervals: Vec<Interval>,
+    spans: Vec<Span>,
+    floor: Au,
+    left_bottom: Au,
+    right_bottom: Au,
+}
+
+/// Configures a new, empty exclusion zone one setting at a time.
+///
+/// Each setting starts out as it is for `Exclusions::default`, so building without changing any
+/// gives an empty, left-to-right, horizontal zone with zero inline size and no block size limit.
+///
+/// ```
+/// extern crate app_units;
+/// extern crate buoyancy;
+///
+/// use app_units::Au;
+/// use buoyancy::exclusions::{Direction, ExclusionsBuilder};
+///
+/// # fn main() {
+/// let exclusions = ExclusionsBuilder::new().inline_size(Au(100))
+///                                          .direction(Direction::Rtl)
+///                                          .inline_start(Au(10))
+///                                          .build()
+///                                          .unwrap();
+/// assert_eq!(exclusions.inline_start(), Au(10));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExclusionsBuilder {
+    inline_size: Au,
+    inline_start: Au,
+    direction: Direction,
+    writing_mode: WritingMode,
+    max_block: Option<Au>,
+}
+
+impl ExclusionsBuilder {
+    /// Creates a builder with every setting at its default.
+    pub fn new() -> ExclusionsBuilder {
+        ExclusionsBuilder {
+            inline_size: Au(0),
+            inline_start: Au(0),
+            direction: Direction::Ltr,
+            writing_mode: WritingMode::HorizontalTb,
+            max_block: None,
+        }
+    }
+
+    /// Sets the inline size of the zone, as passed to `Exclusions::new`.
+    pub fn inline_size(mut self, inline_size: Au) -> ExclusionsBuilder {
+        self.inline_size = inline_size;
+        self
+    }
+
+    /// Sets the inline position of the physical left edge of the zone, as passed to
+    /// `Exclusions::with_inline_start`.
+    pub fn inline_start(mut self, inline_start: Au) -> ExclusionsBuilder {
+        self.inline_start = inline_start;
+        self
+    }
+
+    /// Sets the inline base direction of the zone, as passed to `Exclusions::with_direction`.
+    pub fn direction(mut self, direction: Direction) -> ExclusionsBuilder {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the writing mode of the zone, as `Exclusions::set_writing_mode` does.
+    pub fn writing_mode(mut self, writing_mode: WritingMode) -> ExclusionsBuilder {
+        self.writing_mode = writing_mode;
+        self
+    }
+
+    /// Limits the block size of the container, as `Exclusions::set_max_block` does.
+    pub fn max_block(mut self, max_block: Option<Au>) -> ExclusionsBuilder {
+        self.max_block = max_block;
+        self
+    }
+
+    /// Creates the zone, or returns an error if the inline size or the block size limit is
+    /// negative.
+    pub fn build(&self) -> Result<Exclusions, BuildError> {
+        if self.inline_size < Au(0) {
+            return Err(BuildError::NegativeInlineSize)
+        }
+        match self.max_block {
+            Some(max_block) if max_block < Au(0) => return Err(BuildError::NegativeMaxBlock),
+            Some(_) | None => {}
+        }
+        let mut exclusions = Exclusions::with_direction(self.inline_size, self.direction);
+        exclusions.inline_start = self.inline_start;
+        exclusions.writing_mode = self.writing_mode;
+        exclusions.max_block = self.max_block;
+        Ok(exclusions)
+    }
+}
+
+impl Default for ExclusionsBuilder {
+    fn default() -> ExclusionsBuilder {
+        ExclusionsBuilder::new()
+    }
+}
+
+/// The state of the bands changed by a call to `exclude`, from before the call.
+#[derive(Clone)]
+struct Undo {
+    /// The bands between the top of the first band changed and `region_end`.
+    bands: Vec<(Au, Band)>,
+    /// The bottom of the last band changed, which the call didn't move.
+    region_end: Au,
+    left_bottom: Au,
+    right_bottom: Au,
+}
+
+/// A horizontal strip of an exclusion zone across which the insets on both sides stay the same.
+///
+/// Bands are read-only views into the zone; they are changed only by excluding areas. Insets are
+/// physical, so they are not swapped in a right-to-left zone.
+///
+/// ```
+/// extern crate app_units;
+/// extern crate buoyancy;
+///
+/// use app_units::Au;
+/// use buoyancy::exclusions::{Exclusions, Side, Size};
+///
+/// # fn main() {
+/// let mut exclusions = Exclusions::new(Au(100));
+/// exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+/// let band = exclusions.band_at(Au(5));
+/// assert_eq!((band.left(), band.right(), band.length()), (Au(30), Au(0), Au(20)));
+/// # }
+/// ```
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Band {
+    left: Au,
+    right: Au,
+    length: Au,
+}
+
+impl Band {
+    /// Returns the distance from the left edge of the zone to the nearest excluded area on the
+    /// left side.
+    pub fn left(&self) -> Au {
+        -self.left
+    }
+
+    /// Returns the distance from the right edge of the zone to the nearest excluded area on the
+    /// right side.
+    pub fn right(&self) -> Au {
+        -self.right
+    }
+
+    /// Returns the block size of the band. The final band is infinitely long, so its length
+    /// reaches the maximum representable block position.
+    pub fn length(&self) -> Au {
+        self.length
+    }
+
+    fn new(left: Au, right: Au, length: Au) -> Band {
+        Band {
+            left: left,
+            right: right,
+            length: length,
+        }
+    }
+
+    fn available_size(&self, inline_size: Au) -> Au {
+        saturating_add(saturating_add(inline_size, self.left), self.right)
+    }
+
+    fn get(&self, side: Side) -> Au {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+
+    fn set(&mut self, side: Side, inline_size: Au) {
+        match side {
+            Side::Left => self.left = inline_size,
+            Side::Right => self.right = inline_size,
+        }
+    }
+}
+
+/// A logical point.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Point {
+    /// The origin in the inline direction (normally horizontal).
+    pub inline: Au,
+    /// The origin in the block direction (normally vertical).
+    pub block: Au,
+}
+
+impl Point {
+    /// Creates a new logical point.
+    pub fn new(inline: Au, block: Au) -> Point {
+        Point {
+            inline: inline,
+            block: block,
+        }
+    }
+
+    /// Creates a new logical point from values in CSS pixels, rounded to the nearest app unit.
+    pub fn from_px(inline: f32, block: f32) -> Point {
+        Point::new(Au::from_f32_px(inline), Au::from_f32_px(block))
+    }
+
+    /// Returns the inline and block values of this point in CSS pixels.
+    pub fn to_px(&self) -> (f32, f32) {
+        (self.inline.to_f32_px(), self.block.to_f32_px())
+    }
+}
+
+/// A logical size.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Size {
+    /// The size in the inline direction (normally horizontal).
+    pub inline: Au,
+    /// The size in the block direction (normally vertical).
+    pub block: Au,
+}
+
+impl Size {
+    /// Creates a new logical size.
+    pub fn new(inline: Au, block: Au) -> Size {
+        Size {
+            inline: inline,
+            block: block,
+        }
+    }
+
+    /// Creates a new logical size from values in CSS pixels, rounded to the nearest app unit.
+    pub fn from_px(inline: f32, block: f32) -> Size {
+        Size::new(Au::from_f32_px(inline), Au::from_f32_px(block))
+    }
+
+    /// Returns the inline and block values of this size in CSS pixels.
+    pub fn to_px(&self) -> (f32, f32) {
+        (self.inline.to_f32_px(), self.block.to_f32_px())
+    }
+}
+
+/// Logical margins around an object.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LogicalMargins {
+    /// The margin on the inline-start side (normally left).
+    pub inline_start: Au,
+    /// The margin on the inline-end side (normally right).
+    pub inline_end: Au,
+    /// The margin on the block-start side (normally top).
+    pub block_start: Au,
+    /// The margin on the block-end side (normally bottom).
+    pub block_end: Au,
+}
+
+impl LogicalMargins {
+    /// Creates new logical margins.
+    pub fn new(inline_start: Au, inline_end: Au, block_start: Au, block_end: Au)
+               -> LogicalMargins {
+        LogicalMargins {
+            inline_start,
+            inline_end,
+            block_start,
+            block_end,
+        }
+    }
+
+    /// Returns the size of the margin box of an object with the given border box size.
+    ///
+    /// Negative margins may shrink the margin box, even to a negative size.
+    fn margin_box_size(&self, size: &Size) -> Size {
+        Size::new(size.inline + self.inline_start + self.inline_end,
+                  size.block + self.block_start + self.block_end)
+    }
+}
+
+/// Where an object should be placed to avoid overlapping any excluded area.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Placement {
+    /// The distance from the top left of the zone to the top left of the object.
+    pub origin: Point,
+    /// How much space is available for the object without overlapping any exclusions.
+    ///
+    /// This is never negative, even where the exclusions on either side overlap. Zero means that
+    /// there is no room beside the exclusions and that content should be pushed below them.
+    pub available_inline_size: Au,
+}
+
+impl Placement {
+    fn new(origin: &Point, available_inline_size: Au) -> Placement {
+        Placement {
+            origin: *origin,
+            available_inline_size: cmp::max(available_inline_size, Au(0)),
+        }
+    }
+}
+
+/// Left or right.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// The inline base direction of a zone.
+///
+/// In a right-to-left zone, `Side::Left` refers to the inline-start side, which is the physical
+/// right, and `Side::Right` refers to the inline-end side, which is the physical left. Origins are
+/// always measured from the physical left, so a right-to-left zone is the mirror image of the
+/// equivalent left-to-right one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// The writing mode of a zone, which determines how its logical coordinates map to physical
+/// ones.
+///
+/// Only the conversion between coordinate spaces depends on it; the zone itself is laid out in
+/// logical coordinates either way. Inline positions are measured from the line-left edge, which is
+/// the physical left in a horizontal zone, the physical top in a vertical one, and the physical
+/// bottom in a `sideways-lr` one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WritingMode {
+    HorizontalTb,
+    VerticalRl,
+    VerticalLr,
+    SidewaysLr,
+}
+
+impl WritingMode {
+    /// Converts a logical point in a container of the given logical size to a physical `(x, y)`
+    /// point measured from the top left of the container.
+    pub fn logical_to_physical(&self, point: &Point, container: &Size) -> (Au, Au) {
+        match *self {
+            WritingMode::HorizontalTb => (point.inline, point.block),
+            WritingMode::VerticalRl => (container.block - point.block, point.inline),
+            WritingMode::VerticalLr => (point.block, point.inline),
+            WritingMode::SidewaysLr => (point.block, container.inline - point.inline),
+        }
+    }
+
+    /// Converts a physical `(x, y)` point measured from the top left of a container of the given
+    /// logical size to a logical point. This is the inverse of `logical_to_physical`.
+    pub fn physical_to_logical(&self, physical: (Au, Au), container: &Size) -> Point {
+        let (x, y) = physical;
+        match *self {
+            WritingMode::HorizontalTb => Point::new(x, y),
+            WritingMode::VerticalRl => Point::new(y, container.block - x),
+            WritingMode::VerticalLr => Point::new(y, x),
+            WritingMode::SidewaysLr => Point::new(container.inline - y, x),
+        }
+    }
+}
+
+/// Which sides an object must be placed below all exclusions on, as with the CSS `clear`
+/// property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+/// The error returned by `Exclusions::from_bands` when the bands given don't describe a zone.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InvalidBands;
+
+impl Display for InvalidBands {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.write_str("invalid bands")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for InvalidBands {
+    fn description(&self) -> &str {
+        "bands must be contiguous, start at zero, and narrow going down"
+    }
+}
+
+/// The error returned by `Exclusions::try_new` when the inline size given is negative.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NegativeInlineSize;
+
+impl Display for NegativeInlineSize {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.write_str("negative inline size")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for NegativeInlineSize {
+    fn description(&self) -> &str {
+        "the inline size of a zone must not be negative"
+    }
+}
+
+/// The error returned by `ExclusionsBuilder::build` when the settings don't describe a zone.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuildError {
+    /// The inline size is negative.
+    NegativeInlineSize,
+    /// The block size limit is negative.
+    NegativeMaxBlock,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.write_str(match *self {
+            BuildError::NegativeInlineSize => "negative inline size",
+            BuildError::NegativeMaxBlock => "negative block size limit",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        match *self {
+            BuildError::NegativeInlineSize => "the inline size of a zone must not be negative",
+            BuildError::NegativeMaxBlock => "the block size limit of a zone must not be negative",
+        }
+    }
+}
+
+/// The error returned by `Exclusions::append` when the zones have different inline sizes or
+/// directions.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MismatchedZones;
+
+impl Display for MismatchedZones {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.write_str("mismatched zones")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for MismatchedZones {
+    fn description(&self) -> &str {
+        "zones must have the same inline size and direction to be appended"
+    }
+}
+
+/// An iterator over the bands of an exclusion zone in block order.
+///
+/// Each item is `(block_start, length, left_inset, right_inset)`, where the insets are the
+/// distances from the left and right edges of the zone to the nearest excluded area on each side.
+/// The final band is infinitely long and so ends at the maximum representable block position.
+pub struct Bands<'a> {
+    iter: map::Iter<'a, Au, Band>,
+}
+
+impl<'a> Iterator for Bands<'a> {
+    type Item = (Au, Au, Au, Au);
+    fn next(&mut self) -> Option<(Au, Au, Au, Au)> {
+        self.iter.next().map(|(&block_start, band)| {
+            (block_start, band.length, -band.left, -band.right)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a Exclusions {
+    type Item = (Au, Au, Au, Au);
+    type IntoIter = Bands<'a>;
+    fn into_iter(self) -> Bands<'a> {
+        self.bands()
+    }
+}
+
+/// An iterator over the bands of an exclusion zone in reverse block order, starting with the
+/// infinitely long final band.
+///
+/// Items are the same as those of `Bands`.
+pub struct BandsRev<'a> {
+    iter: map::IterRev<'a, Au, Band>,
+}
+
+impl<'a> Iterator for BandsRev<'a> {
+    type Item = (Au, Au, Au, Au);
+    fn next(&mut self) -> Option<(Au, Au, Au, Au)> {
+        self.iter.next().map(|(&block_start, band)| {
+            (block_start, band.length, -band.left, -band.right)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the available runs of an exclusion zone in block order, one per band.
+///
+/// Each item is `(block_start, inline_start, inline_end)`, where the inline start and end bound
+/// the region between the exclusions on either side, as `Exclusions::available_run` returns them.
+pub struct Runs<'a> {
+    iter: map::Iter<'a, Au, Band>,
+    inline_start: Au,
+    inline_size: Au,
+}
+
+impl<'a> Iterator for Runs<'a> {
+    type Item = (Au, Au, Au);
+    fn next(&mut self) -> Option<(Au, Au, Au)> {
+        let (zone_inline_start, inline_size) = (self.inline_start, self.inline_size);
+        self.iter.next().map(|(&block_start, band)| {
+            let inline_start = zone_inline_start - band.left;
+            (block_start,
+             inline_start,
+             cmp::max(zone_inline_start + inline_size + band.right, inline_start))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the placements of successive line boxes of equal block size, flowing down an
+/// exclusion zone.
+///
+/// Each line box is placed by `place_line` at or below the bottom of the one before it, so a line
+/// that doesn't fit beside a float skips down past it. The iterator ends only when the lines reach
+/// the maximum representable block position.
+pub struct LinePlacements<'a> {
+    exclusions: &'a Exclusions,
+    block_position: Au,
+    line_height: Au,
+}
+
+impl<'a> Iterator for LinePlacements<'a> {
+    type Item = Placement;
+    fn next(&mut self) -> Option<Placement> {
+        if self.block_position == MAX_AU {
+            return None
+        }
+        let placement = match self.exclusions.place_line(self.block_position,
+                                                         self.line_height,
+                                                         Au(1)) {
+            Some(placement) => placement,
+            None => {
+                self.block_position = MAX_AU;
+                return None
+            }
+        };
+        self.block_position = saturating_add(placement.origin.block,
+                                             cmp::max(self.line_height, Au(1)));
+        Some(placement)
+    }
+}
+
+/// Zones are equal if they have the same inline size, inline start, direction, writing mode, and
+/// block size limit, the same bands, floor, and rectangles excluded with `exclude_interval`, and
+/// so place objects alike.
+///
+/// The areas that produced the bands are not compared, so zones built by excluding the same areas
+/// in different orders are equal. The bands are compared in order without cloning them, and the
+/// shapes of the trees holding them don't matter.
+impl PartialEq for Exclusions {
+    fn eq(&self, other: &Exclusions) -> bool {
+        self.inline_size == other.inline_size && self.inline_start == other.inline_start &&
+            self.direction == other.direction && self.writing_mode == other.writing_mode &&
+            self.max_block == other.max_block &&
+            self.floor == other.floor && self.intervals == other.intervals &&
+            self.bands().eq(other.bands())
+    }
+}
+
+/// An empty zone with zero inline size, which can be widened later with `set_inline_size`.
+impl Default for Exclusions {
+    fn default() -> Exclusions {
+        Exclusions::new(Au(0))
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Band {
-    left: Au,
-    right: Au,
-    length: Au,
-}
+#[cfg(feature = "std")]
+impl Debug for Exclusions {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        try!(writeln!(formatter, "Exclusions(inline_size={:?}): bands:", self.inline_size));
+        for (block_position, band) in &self.bands {
+            try!(writeln!(formatter, "    {:?} {:?}", block_position, band));
+        }
+        Ok(())
+    }
+}
+
+impl Exclusions {
+    /// Creates a new rectangular exclusion zone infinitely long in the block direction with the
+    /// given inline size.
+    ///
+    /// The zone starts out with no exclusions in it. Its inline size may be zero, in which case
+    /// every object with a nonzero inline size is placed below all of the exclusions, but it must
+    /// not be negative; this is checked in debug builds. Use `try_new` for inline sizes that
+    /// haven't been checked already.
+    pub fn new(inline_size: Au) -> Exclusions {
+        Exclusions::with_direction(inline_size, Direction::Ltr)
+    }
+
+    /// Creates a new, empty exclusion zone as `new` does, or returns `NegativeInlineSize` if the
+    /// given inline size is negative.
+    pub fn try_new(inline_size: Au) -> Result<Exclusions, NegativeInlineSize> {
+        if inline_size < Au(0) {
+            return Err(NegativeInlineSize)
+        }
+        Ok(Exclusions::new(inline_size))
+    }
+
+    /// Creates a new, empty exclusion zone as `new` does, with the given inline base direction.
+    pub fn with_direction(inline_size: Au, direction: Direction) -> Exclusions {
+        debug_assert!(inline_size >= Au(0), "Exclusions::new(): Negative inline size!");
+        Exclusions {
+            bands: iter::once((Au(0), Band::new(Au(0), Au(0), MAX_AU))).collect(),
+            inline_size,
+            inline_start: Au(0),
+            direction,
+            writing_mode: WritingMode::HorizontalTb,
+            max_block: None,
+            barriers: vec![],
+            excluded: vec![],
+            full_width: vec![],
+            shapes: vec![],
+            intervals: vec![],
+            spans: vec![],
+            floor: Au(0),
+            undo: None,
+            left_bottom: Au(0),
+            right_bottom: Au(0),
+        }
+    }
+
+    /// Creates a new, empty exclusion zone as `new` does, whose physical left edge is at the given
+    /// inline position in the coordinate space of its container, as it is when the container has
+    /// padding or a border.
+    ///
+    /// Every inline position that the zone takes or returns, such as the origin of a placement, is
+    /// then in the coordinate space of the container rather than relative to the zone.
+    pub fn with_inline_start(inline_size: Au, inline_start: Au) -> Exclusions {
+        let mut exclusions = Exclusions::new(inline_size);
+        exclusions.inline_start = inline_start;
+        exclusions
+    }
+
+    /// Creates a new, empty exclusion zone as `new` does, with room in its list of excluded areas
+    /// for at least the given number of floats excluded with `exclude` or `exclude_at`.
+    ///
+    /// Only the area list is reserved; the bands are kept in a tree that allocates a node for each
+    /// band as it is added.
+    pub fn with_capacity(inline_size: Au, floats: usize) -> Exclusions {
+        let mut exclusions = Exclusions::new(inline_size);
+        exclusions.excluded.reserve(floats);
+        exclusions
+    }
+
+    /// Creates a zone with the given inline size from floats that have already been placed.
+    ///
+    /// Each float is given as a side and the rectangle returned by `place`, and is excluded in
+    /// order with `exclude_at`.
+    pub fn from_floats<I>(inline_size: Au, floats: I) -> Exclusions
+                          where I: IntoIterator<Item = (Side, Point, Size)> {
+        let mut exclusions = Exclusions::new(inline_size);
+        for (side, origin, size) in floats {
+            exclusions.exclude_at(side, &origin, &size)
+        }
+        exclusions
+    }
+
+    /// Creates a left-to-right zone with the given inline size from a list of bands, in the form
+    /// that `bands` returns them.
+    ///
+    /// The bands must start at zero, be contiguous and nonempty, and end with an infinitely long
+    /// band. Their insets must lie within the zone and must never grow going down, as in any zone
+    /// without full-width or shape exclusions. Otherwise, `InvalidBands` is returned. Adjacent
+    /// bands with the same insets are merged.
+    ///
+    /// The bands are recorded as the excluded areas that produce them, so the zone can be resized
+    /// and has clearance as though those areas had been excluded with `exclude`.
+    pub fn from_bands(inline_size: Au, bands: Vec<(Au, Au, Au, Au)>)
+                      -> Result<Exclusions, InvalidBands> {
+        let bands: Vec<_> = bands.into_iter().map(|(block_position, length, left, right)| {
+            (block_position, Band::new(-left, -right, length))
+        }).collect();
+        if !bands_are_valid(&bands, inline_size) {
+            return Err(InvalidBands)
+        }
+        let mut exclusions = Exclusions::new(inline_size);
+        for (index, &(block_position, ref band)) in bands.iter().enumerate() {
+            for &side in &[Side::Left, Side::Right] {
+                let inset = -band.get(side);
+                let next_inset = bands.get(index + 1).map_or(Au(0), |next| -next.1.get(side));
+                if inset < next_inset {
+                    return Err(InvalidBands)
+                }
+                if inset > next_inset {
+                    let block_end = block_position + band.length;
+                    exclusions.excluded.push((side, Size::new(inset, block_end)));
+                    exclusions.extend_bottom(side, block_end)
+                }
+            }
+        }
+        exclusions.bands = bands.into_iter().collect();
+        exclusions.compact();
+        Ok(exclusions)
+    }
+
+    /// Returns the inline size of the zone.
+    pub fn inline_size(&self) -> Au {
+        self.inline_size
+    }
+
+    /// Returns the inline position of the physical left edge of the zone in the coordinate space
+    /// of its container.
+    pub fn inline_start(&self) -> Au {
+        self.inline_start
+    }
+
+    /// Returns the inline base direction of the zone.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns the writing mode of the zone.
+    pub fn writing_mode(&self) -> WritingMode {
+        self.writing_mode
+    }
+
+    /// Changes the writing mode of the zone, which is horizontal to begin with.
+    ///
+    /// This affects only `to_physical`; the bands are logical, so they stay as they are.
+    pub fn set_writing_mode(&mut self, writing_mode: WritingMode) {
+        self.writing_mode = writing_mode
+    }
+
+    /// Converts a logical point in the zone to a physical `(x, y)` point measured from the top
+    /// left of a container of the given logical size, according to the writing mode of the zone.
+    pub fn to_physical(&self, point: &Point, container: &Size) -> (Au, Au) {
+        self.writing_mode.logical_to_physical(point, container)
+    }
+
+    /// Returns the block size of the container, if it is limited.
+    pub fn max_block(&self) -> Option<Au> {
+        self.max_block
+    }
+
+    /// Limits the block size of the container, as for a container with a fixed height and
+    /// `overflow: hidden`, or removes the limit if `None` is given.
+    ///
+    /// The bands still go on past the limit, so `place` and the other queries work as they did.
+    /// Only `try_place` and `try_place_block` check it, refusing to place an object whose block
+    /// end would be past it.
+    pub fn set_max_block(&mut self, max_block: Option<Au>) {
+        self.max_block = max_block
+    }
+
+    /// Returns the highest barrier set with `set_barrier` and not yet cleared, if any.
+    pub fn barrier(&self) -> Option<Au> {
+        self.barriers.iter().cloned().min()
+    }
+
+    /// Sets a barrier at the given block position, such as the bottom of a section, that objects
+    /// must be placed entirely above.
+    ///
+    /// Like the limit set with `set_max_block`, barriers are only checked by `try_place` and
+    /// `try_place_block`, which refuse to place an object that would start at or end below a
+    /// barrier. Unlike that limit, barriers belong to a layout pass rather than to the container:
+    /// they form a stack, so that nested sections can each set one and clear it with
+    /// `clear_barrier` when they end, and every barrier on the stack applies. They are not saved
+    /// in snapshots, serialized, or compared.
+    pub fn set_barrier(&mut self, block_position: Au) {
+        self.barriers.push(block_position)
+    }
+
+    /// Clears the barrier set most recently with `set_barrier` and returns it, or returns `None`
+    /// if there are no barriers.
+    pub fn clear_barrier(&mut self) -> Option<Au> {
+        self.barriers.pop()
+    }
+
+    /// Changes the inline size of the zone, recomputing the bands.
+    ///
+    /// Every excluded area is replayed against the new inline size. Areas keep their inline size,
+    /// so those on the right are re-snapped to the new right edge. Objects are not re-placed,
+    /// however; callers that need floats to flow differently at the new size should `clear` and
+    /// lay them out again.
+    ///
+    /// As with `new`, the inline size must not be negative.
+    pub fn set_inline_size(&mut self, new_inline_size: Au) {
+        debug_assert!(new_inline_size >= Au(0),
+                      "Exclusions::set_inline_size(): Negative inline size!");
+        self.inline_size = new_inline_size;
+        self.rebuild();
+    }
+
+    /// Moves every exclusion down by the given distance in the block direction, or up if it is
+    /// negative, as when the content above the floats grows or shrinks.
+    ///
+    /// Moving down leaves nothing excluded above the exclusions, and nothing is placed there
+    /// afterward, since it would be above the floats that were moved. Areas excluded with
+    /// `exclude` no longer reach the top of the zone, so they are kept as though they had been
+    /// excluded with `exclude_span`; `remove` still finds them. Moving up cuts off whatever ends up
+    /// above the top of the zone.
+    ///
+    /// This re-keys the bands in O(n) time, rather than replaying every excluded area as
+    /// `set_inline_size` does.
+    pub fn translate_block(&mut self, delta: Au) {
+        self.undo = None;
+        if delta == Au(0) {
+            return
+        }
+
+        let shift = |block_position: Au| {
+            cmp::min(cmp::max(saturating_add(block_position, delta), Au(0)), MAX_AU - Au(1))
+        };
+        let mut bands = Vec::with_capacity(self.bands.len() + 1);
+        if delta > Au(0) {
+            bands.push((Au(0), Band::new(Au(0), Au(0), shift(Au(0)))))
+        }
+        for (&block_position, band) in &self.bands {
+            let block_start = shift(block_position);
+            let block_end = if block_position + band.length == MAX_AU {
+                MAX_AU
+            } else {
+                shift(block_position + band.length)
+            };
+            if block_end <= block_start {
+                continue
+            }
+            match bands.last_mut() {
+                Some(&mut (_, ref mut last_band)) if last_band.left == band.left &&
+                        last_band.right == band.right => {
+                    last_band.length = last_band.length + block_end - block_start
+                }
+                Some(_) | None => {
+                    bands.push((block_start,
+                                Band::new(band.left, band.right, block_end - block_start)))
+                }
+            }
+        }
+        self.bands = bands.into_iter().collect();
+        self.bands.rebalance();
+
+        // Keep the excluded areas in step with the bands, so that `rebuild` replays them where
+        // they now are.
+        if delta > Au(0) {
+            for &(side, ref size) in &self.excluded {
+                self.spans.push((side, size.inline, Au(0), size.block))
+            }
+            self.excluded.clear();
+        } else {
+            for area in &mut self.excluded {
+                area.1.block = saturating_add(area.1.block, delta)
+            }
+            self.excluded.retain(|area| area.1.block > Au(0));
+        }
+        for range in &mut self.full_width {
+            range.0 = saturating_add(range.0, delta)
+        }
+        for shape in &mut self.shapes {
+            shape.1.block = saturating_add(shape.1.block, delta)
+        }
+        for interval in &mut self.intervals {
+            interval.0 = saturating_add(interval.0, delta);
+            interval.1 = saturating_add(interval.1, delta)
+        }
+        for span in &mut self.spans {
+            span.2 = saturating_add(span.2, delta);
+            span.3 = saturating_add(span.3, delta)
+        }
+
+        let shift_bottom = |block_position: Au| {
+            if block_position > Au(0) { shift(block_position) } else { block_position }
+        };
+        self.left_bottom = shift_bottom(self.left_bottom);
+        self.right_bottom = shift_bottom(self.right_bottom);
+        self.floor = if delta > Au(0) && self.bands.len() > 1 {
+            cmp::max(shift_bottom(self.floor), shift(Au(0)))
+        } else {
+            shift_bottom(self.floor)
+        }
+    }
+
+    /// Adds every exclusion of another zone to this one, moved down to start at the given block
+    /// position, as when joining the fragments of a block container laid out separately.
+    ///
+    /// The exclusions of the other zone are moved as `translate_block` moves them, so they no
+    /// longer reach the top of this zone, and the bands of both zones are then combined, merging
+    /// bands with the same insets where they meet. Both zones must have the same inline size and
+    /// direction; otherwise `MismatchedZones` is returned and this zone is left alone. The rest of
+    /// the state of this zone, such as its writing mode and block size limit, is kept.
+    pub fn append(&mut self, mut other: Exclusions, at_block: Au) -> Result<(), MismatchedZones> {
+        if other.inline_size != self.inline_size || other.direction != self.direction {
+            return Err(MismatchedZones)
+        }
+        other.translate_block(at_block);
+        self.excluded.extend(other.excluded);
+        self.full_width.extend(other.full_width);
+        self.shapes.extend(other.shapes);
+        self.intervals.extend(other.intervals);
+        self.spans.extend(other.spans);
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Returns a new zone covering only the given inline range of this one, as for floats inside
+    /// an indented block, so that objects can be placed within that range alone.
+    ///
+    /// The range is given in the same coordinate space as the origins this zone returns, and so
+    /// are the origins the new zone returns. Any part of it outside this zone is cut off.
+    /// Exclusions entirely outside the range are dropped, and those partly inside it are clipped
+    /// to it. The new zone has the same direction, writing mode, and block size limit as this one.
+    /// It also has the same barriers, copied unchanged, since a barrier is a block position that
+    /// applies across the whole inline size and so has no inline extent to clip.
+    pub fn subregion(&self, inline_range: (Au, Au)) -> Exclusions {
+        let range_start = cmp::max(inline_range.0 - self.inline_start, Au(0));
+        let range_end = cmp::max(cmp::min(inline_range.1 - self.inline_start, self.inline_size),
+                                 range_start);
+        let mut subregion = Exclusions::with_direction(range_end - range_start, self.direction);
+        subregion.inline_start = self.inline_start + range_start;
+        subregion.writing_mode = self.writing_mode;
+        subregion.max_block = self.max_block;
+        subregion.barriers = self.barriers.clone();
+
+        // Measure each inset from the edge of the range instead of the edge of this zone.
+        let clip = |side: Side, inset: Au| {
+            let inset = cmp::min(inset, self.inline_size);
+            match self.physical_side(side) {
+                Side::Left => inset - range_start,
+                Side::Right => range_end - (self.inline_size - inset),
+            }
+        };
+        for &(side, ref size) in &self.excluded {
+            subregion.exclude(side, &Size::new(clip(side, size.inline), size.block))
+        }
+        for &(block_start, block_size) in &self.full_width {
+            subregion.exclude_full_width(block_start, block_size)
+        }
+        for &(side, ref origin, ref vertices) in &self.shapes {
+            subregion.exclude_shape(side, origin, vertices)
+        }
+        for &(block_start, block_end, inline_start, inline_end) in &self.intervals {
+            let inline_start = cmp::max(inline_start, range_start) + self.inline_start;
+            let inline_end = cmp::min(inline_end, range_end) + self.inline_start;
+            subregion.exclude_interval(block_start,
+                                       block_end - block_start,
+                                       (inline_start, inline_end))
+        }
+        for &(side, inset, block_start, block_end) in &self.spans {
+            subregion.exclude_span(side, clip(side, inset), block_start, block_end)
+        }
+        subregion.undo = None;
+        subregion
+    }
+
+    /// Places an object so that it does not overlap any exclusions according to the CSS float
+    /// placement rules.
+    ///
+    /// The object is aligned either to the left or right, depending on the size.
+    pub fn place(&mut self, alignment: Side, size: &Size) -> Placement {
+        self.place_at_or_below(alignment, size, Au(0))
+    }
+
+    /// Places an object as `place` does, unless it is wider than the zone and so could not
+    /// actually fit in any band, or it would end past the limit set with `set_max_block` or cross
+    /// a barrier set with `set_barrier`. In any of these cases `None` is returned.
+    ///
+    /// Unlike `place`, this does not require mutable access to the exclusions.
+    pub fn try_place(&self, alignment: Side, size: &Size) -> Option<Placement> {
+        if size.inline > self.inline_size {
+            return None
+        }
+        self.within_limits(self.place_at_or_below(alignment, size, Au(0)), size)
+    }
+
+    /// Places a box as `place_block` does, unless it doesn't fit, as with `try_place`.
+    pub fn try_place_block(&self, alignment: Side, size: &Size) -> Option<Placement> {
+        if size.inline > self.inline_size {
+            return None
+        }
+        self.within_limits(self.block_placement(alignment, size), size)
+    }
+
+    /// Places an object as `place` does, but never above the given block position.
+    ///
+    /// Bands that end at or above that position are skipped, even if they have room.
+    pub fn place_below(&mut self, alignment: Side, size: &Size, min_block_position: Au)
+                       -> Placement {
+        self.place_at_or_below(alignment, size, min_block_position)
+    }
+
+    /// Places an object as `place` does, and also returns the inset that the object will occupy
+    /// on the given side: the distance from the edge of the zone on that side to the far edge of
+    /// the object.
+    ///
+    /// This is the inset to pass to `exclude_span` to exclude the object once it is placed, or
+    /// the inline size to pass to `exclude`, without working it out from the origin.
+    pub fn place_with_inset(&mut self, alignment: Side, size: &Size) -> (Placement, Au) {
+        let placement = self.place(alignment, size);
+        let inset = self.area_from_rect(alignment, &placement.origin, size).inline;
+        (placement, inset)
+    }
+
+    /// Returns the block position of the first band that an object of the given inline size fits
+    /// in, along with the inline size available there, whichever side the object is aligned to.
+    /// The available inline size is never negative.
+    ///
+    /// This is the search that `place` and `try_place` are built on, for callers that align
+    /// objects themselves. It ignores rectangles excluded with `exclude_interval`, which an object
+    /// may or may not overlap depending on its side, so it gives the block position that `place`
+    /// would only when the zone has no such rectangles. Objects wider than the zone fit only in
+    /// the final band.
+    pub fn first_fit(&self, width: Au) -> (Au, Au) {
+        let min_block_position = self.min_block_for_width(width, Au(0));
+        let (block_position, band) = self.first_fit_at_or_below(width, min_block_position);
+        (block_position, cmp::max(band.available_size(self.inline_size), Au(0)))
+    }
+
+    /// Places an object in the middle of the first band wide enough for it, as for a block with
+    /// `margin: auto` on both inline sides, rather than against either side.
+    ///
+    /// The object is centered between the exclusions on either side. When the space left over is
+    /// odd, the extra app unit goes on the right. An object wider than the space is placed below
+    /// every exclusion and against the left edge, as auto margins never go negative. Like `place`,
+    /// this avoids rectangles excluded with `exclude_interval`.
+    pub fn place_centered(&mut self, size: &Size) -> Placement {
+        let inline_size = self.inline_size;
+        self.align_at_or_below(size, Au(0), |band| {
+            let available_size = cmp::max(band.available_size(inline_size), Au(0));
+            let slack = cmp::max(available_size - size.inline, Au(0));
+            -band.left + slack / 2
+        })
+    }
+
+    /// Places a box that can't be split across bands, such as a block element, so that every band
+    /// it spans is wide enough for it.
+    ///
+    /// `place` only requires the band at the top of the object to be wide enough. Below the
+    /// floor, bands never narrow going down, so the two agree for zones built with `exclude`;
+    /// this method checks the whole block size anyway and moves the box below any band that is
+    /// too narrow. Like `place`, it also moves the box below rectangles excluded with
+    /// `exclude_interval` that it would overlap.
+    ///
+    /// This also suits floated replaced elements such as images, whose whole rectangle must be
+    /// clear. The origin returned can be passed straight to `exclude_at` with the same side and
+    /// size.
+    pub fn place_block(&mut self, alignment: Side, size: &Size) -> Placement {
+        self.block_placement(alignment, size)
+    }
+
+    /// Places a float as `place` does and then excludes the area it occupies, extended to the top
+    /// of the zone and to the edge on the given side, so that later floats flow around it.
+    pub fn place_and_exclude(&mut self, side: Side, size: &Size) -> Placement {
+        let placement = self.place(side, size);
+        let area = self.area_from_rect(side, &placement.origin, size);
+        self.exclude(side, &area);
+        placement
+    }
+
+    /// Places and excludes each of the given floats in order, as `place_and_exclude` does, and
+    /// returns their placements.
+    pub fn place_all(&mut self, floats: &[(Side, Size)]) -> Vec<Placement> {
+        floats.iter().map(|&(side, ref size)| self.place_and_exclude(side, size)).collect()
+    }
+
+    /// Places and excludes each of the given floats as `place_all` does, but in whichever order
+    /// keeps them highest rather than in the order given, and returns their placements in the
+    /// order given.
+    ///
+    /// This is not CSS float placement. It is meant for layouts like masonry galleries, where the
+    /// floats may be reordered to save space. A float may therefore end up above one given before
+    /// it, or beside a float that it would have had to go below. At each step, the remaining
+    /// float that can be placed highest is placed next, with ties going to the one given first.
+    /// This takes O(n²) placements for n floats.
+    pub fn pack(&mut self, floats: &[(Side, Size)]) -> Vec<Placement> {
+        let mut placements = vec![None; floats.len()];
+        let mut remaining: Vec<usize> = (0..floats.len()).collect();
+        while !remaining.is_empty() {
+            let position = remaining.iter().map(|&index| {
+                let (side, ref size) = floats[index];
+                self.place_at_or_below(side, size, Au(0)).origin.block
+            }).enumerate().min_by_key(|&(_, block_position)| block_position).unwrap().0;
+            let index = remaining.remove(position);
+            let (side, ref size) = floats[index];
+            placements[index] = Some(self.place_and_exclude(side, size))
+        }
+        placements.into_iter().map(|placement| placement.unwrap()).collect()
+    }
+
+    /// Places an object on whichever side gives it the highest block position, and returns the
+    /// side chosen so that it can be passed to `exclude`.
+    ///
+    /// Only the block positions of the two placements are compared. If they are equal, the
+    /// placement on the preferred side is returned, even though it may be in a different run,
+    /// and have a different available inline size, than the placement on the other side. Each
+    /// band has a single available inline size, so the two sides give different block positions
+    /// only where a rectangle excluded with `exclude_interval` is in the way on one side.
+    pub fn place_preferred(&mut self, preferred: Side, size: &Size) -> (Side, Placement) {
+        let placement = self.place(preferred, size);
+        let opposite = match preferred {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        let opposite_placement = self.place(opposite, size);
+        if opposite_placement.origin.block < placement.origin.block {
+            (opposite, opposite_placement)
+        } else {
+            (preferred, placement)
+        }
+    }
+
+    /// Places an object as `place` does, but also moves it below the bottom of every exclusion
+    /// on the sides it clears, as with the CSS `clear` property.
+    pub fn place_with_clear(&mut self, alignment: Side, clear: Clear, size: &Size) -> Placement {
+        let min_block_position = self.min_block_for_clear(clear);
+        self.place_at_or_below(alignment, size, min_block_position)
+    }
+
+    /// Places an object with margins as `place` does.
+    ///
+    /// The margin box of the object is used to avoid exclusions, but the returned origin is that
+    /// of its border box, which has the given size.
+    ///
+    /// Negative margins pull the border box over neighboring exclusions or past the edge of the
+    /// zone, so the returned origin may have a negative inline position.
+    pub fn place_with_margins(&mut self,
+                              alignment: Side,
+                              size: &Size,
+                              margins: &LogicalMargins)
+                              -> Placement {
+        let mut placement = self.place(alignment, &margins.margin_box_size(size));
+        placement.origin.inline += match self.direction {
+            Direction::Ltr => margins.inline_start,
+            Direction::Rtl => margins.inline_end,
+        };
+        placement.origin.block += margins.block_start;
+        placement
+    }
+
+    /// Finds room for a line box of the given block size, at or below the given block position,
+    /// whose available inline size is at least the given minimum throughout.
+    ///
+    /// Unlike `place`, the line box may span several bands, all of which must be wide enough.
+    /// The returned origin and available inline size describe the central run between the
+    /// exclusions on either side. Returns `None` if no band is wide enough.
+    ///
+    /// Only the bands are consulted, so rectangles excluded with `exclude_interval` are ignored
+    /// and the line box may overlap them. Callers using such rectangles can split the line box
+    /// into the runs that `runs_at` returns.
+    pub fn place_line(&self, block_start: Au, block_size: Au, min_inline_size: Au)
+                      -> Option<Placement> {
+        // The block start, inline start, and inline end of the run being considered.
+        let mut run: Option<(Au, Au, Au)> = None;
+        for (&band_block_start, band) in self.bands.iter() {
+            let band_block_end = band_block_start + band.length;
+            if band_block_end <= block_start && band_block_end != MAX_AU {
+                continue
+            }
+            let (band_inline_start, band_inline_end) = (-band.left, self.inline_size + band.right);
+            if band_inline_end - band_inline_start < min_inline_size {
+                run = None;
+                continue
+            }
+            run = match run {
+                Some((run_block_start, run_inline_start, run_inline_end))
+                        if cmp::min(run_inline_end, band_inline_end) -
+                        cmp::max(run_inline_start, band_inline_start) >= min_inline_size => {
+                    Some((run_block_start,
+                          cmp::max(run_inline_start, band_inline_start),
+                          cmp::min(run_inline_end, band_inline_end)))
+                }
+                Some(_) | None => {
+                    Some((cmp::max(band_block_start, block_start),
+                          band_inline_start,
+                          band_inline_end))
+                }
+            };
+            let (run_block_start, run_inline_start, run_inline_end) = run.unwrap();
+            if band_block_end - run_block_start >= block_size || band_block_end == MAX_AU {
+                let origin = Point::new(self.inline_start + run_inline_start, run_block_start);
+                return Some(Placement::new(&origin, run_inline_end - run_inline_start))
+            }
+        }
+        None
+    }
+
+    /// Returns an iterator over the placements of line boxes of the given block size, starting
+    /// at the given block position and flowing down the zone.
+    ///
+    /// Each line box is placed where there is room for at least one app unit of content beside
+    /// the floats throughout its block size, directly below the line box before it if possible.
+    /// Line boxes of zero block size advance by one app unit so that the iterator makes
+    /// progress. As with `place_line`, rectangles excluded with `exclude_interval` are ignored.
+    pub fn line_placements<'a>(&'a self, block_start: Au, line_height: Au) -> LinePlacements<'a> {
+        LinePlacements {
+            exclusions: self,
+            block_position: cmp::max(block_start, Au(0)),
+            line_height,
+        }
+    }
+
+    /// Excludes a rectangular area of the given size, preventing any objects from being placed
+    /// within it.
+    ///
+    /// The excluded area touches the top left or top right of the zone, depending on the side. An
+    /// area wider than the zone is clipped to the zone's inline size, though it is remembered at
+    /// its full size in case the zone is later widened.
+    ///
+    /// An area with a zero or negative size, such as the margin box of a float with large negative
+    /// margins, lies entirely outside the zone and so excludes nothing. This includes an area with
+    /// a nonzero inline size but no block size: every band has a positive length, so there is no
+    /// band for it to narrow, and nothing can flow beside it anyway. Callers that need later
+    /// floats to stay below such a float should pass its block position to `place_below`.
+    pub fn exclude(&mut self, side: Side, size: &Size) {
+        if size.inline <= Au(0) || size.block <= Au(0) {
+            self.undo = None;
+            return
+        }
+
+        self.excluded.push((side, *size));
+        let side = self.physical_side(side);
+        let inline_size = cmp::min(size.inline, self.inline_size);
+        self.save_undo(side, inline_size, size.block);
+        self.extend_bottom(side, size.block);
+        self.exclude_range(side, inline_size, Au(0), size.block)
+    }
+
+    /// Undoes the most recent call to `exclude`, returning the zone to exactly the state it was
+    /// in before, and returns true. This is cheaper than `remove`, since only the bands changed
+    /// by that call are restored.
+    ///
+    /// Only one exclusion can be undone. Returns false, doing nothing, if the last change to the
+    /// zone was not an exclusion by `exclude` (or one of the methods that call it), if that
+    /// exclusion excluded nothing, or if it has already been undone.
+    pub fn undo_last_exclude(&mut self) -> bool {
+        let undo = match self.undo.take() {
+            Some(undo) => undo,
+            None => return false,
+        };
+        let region_start = undo.bands[0].0;
+        let keys: Vec<Au> = self.bands
+                                .range(Bound::Included(region_start),
+                                       Bound::Excluded(undo.region_end))
+                                .map(|(&block_position, _)| block_position)
+                                .collect();
+        for block_position in &keys {
+            self.bands.remove(block_position);
+        }
+        self.bands.extend(undo.bands);
+        self.excluded.pop();
+        self.left_bottom = undo.left_bottom;
+        self.right_bottom = undo.right_bottom;
+        true
+    }
+
+    /// Excludes the margin box of an object whose border box has the given size, as `exclude`
+    /// does.
+    ///
+    /// The margin box touches the top left or top right of the zone, depending on the side.
+    pub fn exclude_with_margins(&mut self, side: Side, size: &Size, margins: &LogicalMargins) {
+        self.exclude(side, &margins.margin_box_size(size))
+    }
+
+    /// Excludes an object that has been placed, given the side it was placed on and the origin
+    /// returned by `place`.
+    ///
+    /// The inset is computed from the inline position of the origin, and the area is extended to
+    /// the top of the zone and to the edge on that side as `remove` does, so this is equivalent to
+    /// calling `exclude` with the size of that area.
+    pub fn exclude_at(&mut self, side: Side, origin: &Point, size: &Size) {
+        let area = self.area_from_rect(side, origin, size);
+        self.exclude(side, &area)
+    }
+
+    /// Excludes a placed object as `exclude_at` does, and returns the inline size left available
+    /// at the object's block start afterward.
+    ///
+    /// The result is what `available_width(origin.block, Au(0))` would return.
+    pub fn exclude_reporting(&mut self, side: Side, origin: &Point, size: &Size) -> Au {
+        self.exclude_at(side, origin, size);
+        cmp::max(self.band_at(origin.block).available_size(self.inline_size), Au(0))
+    }
+
+    /// Excludes many placed objects at once, as though `exclude_at` had been called for each of
+    /// them in the given order.
+    ///
+    /// Each object is given as a side and the rectangle returned by `place`.
+    pub fn exclude_many(&mut self, objects: &[(Side, Point, Size)]) {
+        for &(side, ref origin, ref size) in objects {
+            self.exclude_at(side, origin, size)
+        }
+    }
+
+    /// Excludes a rectangle that touches neither edge of the zone, such as a float positioned in
+    /// the middle of a line, across the given block range.
+    ///
+    /// The inline range is given as the inline start and end of the rectangle. Such a rectangle
+    /// splits the line into a run on either side of it, which `runs_at` returns. Objects are
+    /// placed and tested for overlap around it, but the bands and the queries built on them, such
+    /// as `insets_at` and `place_line`, only describe the exclusions touching either edge. Those
+    /// stay as fast as they were, while each of these rectangles adds to the cost of placement.
+    pub fn exclude_interval(&mut self, block_start: Au, block_size: Au, inline: (Au, Au)) {
+        self.undo = None;
+        let (inline_start, inline_end) = (inline.0 - self.inline_start,
+                                          inline.1 - self.inline_start);
+        if block_size <= Au(0) || inline_end <= inline_start {
+            return
+        }
+        self.intervals.push((block_start,
+                             saturating_add(block_start, block_size),
+                             inline_start,
+                             inline_end))
+    }
+
+    /// Excludes the area from the edge on the given side out to the given inset, across only the
+    /// block range from `block_start` to `block_end`, as a float that has already been placed
+    /// there occupies.
+    ///
+    /// Unlike `exclude`, the area doesn't reach up to the top of the zone, so bands above
+    /// `block_start` are left as they were. Nothing is placed above `block_start` afterward, since
+    /// placing an object there would put it above an earlier float.
+    ///
+    /// An empty span excludes nothing, as an empty area passed to `exclude` does, and so doesn't
+    /// move the floor either.
+    pub fn exclude_span(&mut self, side: Side, inline_inset: Au, block_start: Au, block_end: Au) {
+        self.undo = None;
+        if inline_inset <= Au(0) || block_end <= block_start {
+            return
+        }
+
+        self.spans.push((side, inline_inset, block_start, block_end));
+        let side = self.physical_side(side);
+        let inline_size = self.inline_size;
+        self.exclude_range(side, cmp::min(inline_inset, inline_size), block_start, block_end);
+        self.extend_bottom(side, block_end);
+        self.floor = cmp::max(self.floor, block_start)
+    }
+
+    /// Excludes the entire inline size of the zone across the given block range, as a block that
+    /// spans the whole line would.
+    ///
+    /// Nothing is placed above the end of the range afterward, even where there is room, since
+    /// placing an object there would put it above an earlier one.
+    pub fn exclude_full_width(&mut self, block_start: Au, block_size: Au) {
+        self.undo = None;
+        if block_size <= Au(0) {
+            return
+        }
+
+        self.full_width.push((block_start, block_size));
+        let (side, inline_size) = (self.physical_side(Side::Left), self.inline_size);
+        let block_end = saturating_add(block_start, block_size);
+        self.exclude_range(side, inline_size, block_start, block_end);
+        self.floor = cmp::max(self.floor, block_end)
+    }
+
+    /// Excludes the polygon with the given vertices, as a float with `shape-outside` does.
+    ///
+    /// The vertices are `(inline, block)` offsets from the given origin, in order around the
+    /// polygon. Each band that the polygon spans is excluded up to the polygon's farthest extent
+    /// from the edge on the given side anywhere within that band, splitting bands at the vertices
+    /// as needed. A slanted edge is therefore excluded as a staircase with one step per band, and
+    /// callers wanting finer steps can add vertices along it.
+    ///
+    /// As with `exclude_full_width`, nothing is placed above the bottom of the polygon afterward.
+    pub fn exclude_shape(&mut self, side: Side, origin: &Point, vertices: &[(Au, Au)]) {
+        self.undo = None;
+        let (block_start, block_end) = match shape_block_range(origin, vertices) {
+            Some(range) => range,
+            None => return,
+        };
+
+        self.shapes.push((side, *origin, vertices.to_vec()));
+        let side = self.physical_side(side);
+        let mut boundaries: Vec<Au> =
+            vertices.iter().map(|vertex| origin.block + vertex.1).collect();
+        boundaries.extend(self.bands
+                              .range(Bound::Excluded(block_start), Bound::Excluded(block_end))
+                              .map(|(&block_position, _)| block_position));
+        boundaries.sort();
+        boundaries.dedup();
+        for boundary in boundaries.windows(2) {
+            let (inline_start, inline_end) = shape_inline_extent(vertices,
+                                                                 boundary[0] - origin.block,
+                                                                 boundary[1] - origin.block);
+            let origin_inline = origin.inline - self.inline_start;
+            let inset = match side {
+                Side::Left => origin_inline + inline_end,
+                Side::Right => self.inline_size - origin_inline - inline_start,
+            };
+            if inset > Au(0) {
+                let inline_size = self.inline_size;
+                self.exclude_range(side, cmp::min(inset, inline_size), boundary[0], boundary[1])
+            }
+        }
+        self.extend_bottom(side, block_end);
+        self.floor = cmp::max(self.floor, block_end)
+    }
+
+    /// Returns the lowest block position reached by any area excluded on the given side, or zero
+    /// if nothing has been excluded on that side.
+    ///
+    /// This is the block position that an object clearing that side must be placed at or below.
+    pub fn clearance(&self, side: Side) -> Au {
+        match self.physical_side(side) {
+            Side::Left => self.left_bottom,
+            Side::Right => self.right_bottom,
+        }
+    }
+
+    /// Returns the block position that an object with the given `clear` value must be placed at
+    /// or below.
+    ///
+    /// Like `Side`, `Clear::Left` and `Clear::Right` are relative to the inline base direction,
+    /// so in a right-to-left zone `Clear::Left` clears the exclusions on the physical right.
+    pub fn min_block_for_clear(&self, clear: Clear) -> Au {
+        match clear {
+            Clear::None => Au(0),
+            Clear::Left => self.clearance(Side::Left),
+            Clear::Right => self.clearance(Side::Right),
+            Clear::Both => cmp::max(self.clearance(Side::Left), self.clearance(Side::Right)),
+        }
+    }
+
+    /// Returns an iterator over the bands in block order, without cloning the band structure.
+    pub fn bands<'a>(&'a self) -> Bands<'a> {
+        Bands {
+            iter: self.bands.iter(),
+        }
+    }
+
+    /// Returns an iterator over the available run of each band, in block order.
+    ///
+    /// Where the exclusions on either side of a band meet or overlap, its run is empty and its
+    /// start and end are equal.
+    pub fn runs<'a>(&'a self) -> Runs<'a> {
+        Runs {
+            iter: self.bands.iter(),
+            inline_start: self.inline_start,
+            inline_size: self.inline_size,
+        }
+    }
+
+    /// Returns the block positions where the available inline size changes, each with the inline
+    /// size available from there down to the next one, starting with the top of the zone.
+    ///
+    /// Unlike `bands`, adjacent bands with different insets but the same available inline size
+    /// are reported once, so line layout can tell where the width of its lines actually steps.
+    /// As in `first_fit`, the available inline size is never negative, and rectangles excluded
+    /// with `exclude_interval` are ignored.
+    pub fn width_breakpoints(&self) -> Vec<(Au, Au)> {
+        let mut breakpoints: Vec<(Au, Au)> = vec![];
+        for (&block_position, band) in &self.bands {
+            let available_size = cmp::max(band.available_size(self.inline_size), Au(0));
+            if breakpoints.last().map(|breakpoint| breakpoint.1) != Some(available_size) {
+                breakpoints.push((block_position, available_size))
+            }
+        }
+        breakpoints
+    }
+
+    /// Returns an iterator over the bands in reverse block order, from the bottom up.
+    ///
+    /// This is useful for finding the last few bands without visiting all of them.
+    pub fn bands_rev<'a>(&'a self) -> BandsRev<'a> {
+        BandsRev {
+            iter: self.bands.iter_rev(),
+        }
+    }
+
+    /// Returns the total area covered by the insets of all bands, in square app units.
+    ///
+    /// Where the exclusions on either side overlap, the overlap is counted twice. The area is
+    /// returned as an `i64`, since it easily exceeds the range of `Au`.
+    pub fn excluded_area(&self) -> i64 {
+        self.bands.iter().filter(|&(&block_position, band)| {
+            block_position + band.length != MAX_AU
+        }).map(|(_, band)| {
+            -(band.left.0 as i64 + band.right.0 as i64) * band.length.0 as i64
+        }).sum()
+    }
+
+    /// Returns the min-content inline contribution of the exclusions, which is the widest inset on
+    /// either side of any band.
+    ///
+    /// The final infinitely long band is skipped.
+    pub fn min_content_inline(&self) -> Au {
+        self.bands.iter().filter(|&(&block_position, band)| {
+            block_position + band.length != MAX_AU
+        }).map(|(_, band)| cmp::max(-band.left, -band.right)).max().unwrap_or(Au(0))
+    }
+
+    /// Returns the max-content inline contribution of the exclusions, which is the widest
+    /// combined inset on both sides of any band.
+    ///
+    /// The final infinitely long band is skipped.
+    pub fn max_content_inline(&self) -> Au {
+        self.bands.iter().filter(|&(&block_position, band)| {
+            block_position + band.length != MAX_AU
+        }).map(|(_, band)| saturating_add(-band.left, -band.right)).max().unwrap_or(Au(0))
+    }
+
+    /// Returns the block start and end of the region containing every band with an exclusion on
+    /// either side, or `None` if nothing is excluded.
+    pub fn bounds(&self) -> Option<(Au, Au)> {
+        let mut excluded_bands = self.bands.iter().filter(|&(_, band)| {
+            band.left != Au(0) || band.right != Au(0)
+        });
+        let (&block_start, band) = excluded_bands.next()?;
+        let block_end = match excluded_bands.last() {
+            Some((&block_position, last_band)) => block_position + last_band.length,
+            None => block_start + band.length,
+        };
+        Some((block_start, block_end))
+    }
+
+    /// Returns the bottom of the lowest band with an exclusion on either side, or zero if nothing
+    /// is excluded.
+    ///
+    /// This is the height that the exclusions contribute to the block container holding them.
+    pub fn height(&self) -> Au {
+        self.bounds().map_or(Au(0), |(_, block_end)| block_end)
+    }
+
+    /// Returns the outline of the exclusions on the given side, from the top of the zone to the
+    /// bottom of the lowest band with an exclusion on that side, as a staircase polyline.
+    ///
+    /// The outline starts at the top of the zone on the edge of that side and alternates between
+    /// horizontal and vertical segments, with no duplicate or collinear points. It is empty if
+    /// nothing is excluded on that side.
+    pub fn outline(&self, side: Side) -> Vec<Point> {
+        let side = self.physical_side(side);
+        let inset = |band: &(Au, Au, Au, Au)| {
+            match side {
+                Side::Left => band.2,
+                Side::Right => band.3,
+            }
+        };
+        let block_end = match self.bands().filter(|band| inset(band) != Au(0)).last() {
+            Some((block_start, length, _, _)) => block_start + length,
+            None => return vec![],
+        };
+        let inline_position = |inset: Au| {
+            match side {
+                Side::Left => self.inline_start + inset,
+                Side::Right => self.inline_start + self.inline_size - inset,
+            }
+        };
+        let mut outline = vec![Point::new(inline_position(Au(0)), Au(0))];
+        for band in self.bands() {
+            let (block_start, length, inline) = (band.0, band.1, inline_position(inset(&band)));
+            push_outline_point(&mut outline, Point::new(inline, block_start));
+            if block_start >= block_end {
+                break
+            }
+            push_outline_point(&mut outline, Point::new(inline, block_start + length));
+        }
+        outline
+    }
+
+    /// Draws the exclusions as a grid of text with the given number of columns and rows, for
+    /// debugging.
+    ///
+    /// The grid spans the inline size of the zone and its `height`, so the final infinitely long
+    /// band is cut off there. Each cell is `#` if the point at its center is excluded and `.`
+    /// otherwise, and each row ends with a newline.
+    pub fn render_ascii(&self, cols: usize, rows: usize) -> String {
+        let block_size = self.height().0 as i64;
+        let mut ascii = String::with_capacity((cols + 1) * rows);
+        for row in 0..rows as i64 {
+            let block = Au((block_size * (2 * row + 1) / (2 * rows as i64)) as i32);
+            for col in 0..cols as i64 {
+                let inline = self.inline_size.0 as i64 * (2 * col + 1) / (2 * cols as i64);
+                let point = Point::new(self.inline_start + Au(inline as i32), block);
+                ascii.push(if self.is_excluded(&point) { '#' } else { '.' });
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// Draws the exclusions as an SVG image, for debugging.
+    ///
+    /// Each band inset on either side becomes a `<rect>`, and each rectangle excluded with
+    /// `exclude_interval` becomes another. Coordinates are in app units relative to the zone, and
+    /// the image spans the inline size of the zone and its `height`.
+    pub fn render_svg(&self) -> String {
+        let height = self.height();
+        let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+                               height=\"{}\">\n",
+                              self.inline_size.0,
+                              height.0);
+        for (&block_position, band) in &self.bands {
+            if block_position >= height {
+                break
+            }
+            if band.left < Au(0) {
+                push_svg_rect(&mut svg, Au(0), block_position, -band.left, band.length);
+            }
+            if band.right < Au(0) {
+                push_svg_rect(&mut svg,
+                              self.inline_size + band.right,
+                              block_position,
+                              -band.right,
+                              band.length);
+            }
+        }
+        for &(block_start, block_end, inline_start, inline_end) in &self.intervals {
+            push_svg_rect(&mut svg,
+                          inline_start,
+                          block_start,
+                          inline_end - inline_start,
+                          block_end - block_start);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Returns the number of bands, including the final infinitely long band.
+    ///
+    /// This is useful for detecting inputs that defeat band merging.
+    pub fn band_count(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Returns true if nothing is excluded anywhere in the zone, so that every object can be
+    /// placed at the top of it.
+    ///
+    /// This is the case when the final infinitely long band is the only band, no rectangles have
+    /// been excluded with `exclude_interval`, and the floor is at the top of the zone. A
+    /// full-width exclusion in a zone with no inline size, or a shape or span lying outside the
+    /// zone, leaves the bands alone but still moves the floor down.
+    pub fn is_empty(&self) -> bool {
+        self.bands.len() == 1 && self.intervals.is_empty() && self.floor == Au(0)
+    }
+
+    /// Returns the maximum and average depth of the tree holding the bands, as
+    /// `SplayMap::depth_stats` does.
+    ///
+    /// Queries through `&self`, such as `try_place` and `band_at`, don't splay the tree, so a
+    /// maximum depth far above log2 of `band_count` means they are slower than they could be. If
+    /// so, `compact` rebalances the tree.
+    pub fn tree_depth(&self) -> (usize, f64) {
+        self.bands.depth_stats()
+    }
+
+    /// Returns the number of bands that intersect the block range from `block_lo` to `block_hi`,
+    /// counting the bands that the range only partly covers. An empty range has no bands.
+    pub fn band_count_in(&self, block_lo: Au, block_hi: Au) -> usize {
+        if block_lo >= block_hi {
+            return 0
+        }
+        let first_block_position = self.band_entry_at(block_lo).0;
+        self.bands.range(Bound::Included(first_block_position), Bound::Excluded(block_hi)).count()
+    }
+
+    /// Checks the invariants of the bands, panicking with a description of the first one violated.
+    ///
+    /// The invariants are:
+    ///
+    /// * The first band starts at zero, and each band starts where the one above it ends.
+    ///
+    /// * Every band has a positive length, and the last band ends at `i32::MAX`. No other band
+    ///   ends there, so the last band is the only infinitely long one.
+    ///
+    /// * No inset is negative or wider than the zone.
+    ///
+    /// * No two adjacent bands have the same insets on both sides.
+    ///
+    /// * Unless a full-width range or shape has been excluded, the insets on each side never grow
+    ///   going down the zone.
+    ///
+    /// Bands built by deserialization may not be merged, so those may fail the fourth check until
+    /// `compact` is called.
+    #[cfg(any(test, feature = "validate"))]
+    pub fn assert_invariants(&self) {
+        let mut next_block_position = Au(0);
+        let mut previous_band: Option<&Band> = None;
+        for (&block_position, band) in &self.bands {
+            assert!(next_block_position != MAX_AU,
+                    "band at {:?} follows the infinitely long band", block_position);
+            assert!(block_position == next_block_position,
+                    "band at {:?} doesn't start where the band above it ends, at {:?}",
+                    block_position,
+                    next_block_position);
+            assert!(band.length > Au(0),
+                    "band at {:?} has nonpositive length {:?}", block_position, band.length);
+            assert!(band.length <= MAX_AU - block_position,
+                    "band at {:?} with length {:?} ends past i32::MAX",
+                    block_position,
+                    band.length);
+            for &side in &[Side::Left, Side::Right] {
+                let inset = -band.get(side);
+                assert!(inset >= Au(0) && inset <= self.inline_size,
+                        "band at {:?} has {:?} inset {:?} outside the zone of inline size {:?}",
+                        block_position,
+                        side,
+                        inset,
+                        self.inline_size);
+            }
+            if let Some(previous_band) = previous_band {
+                assert!(band.left != previous_band.left || band.right != previous_band.right,
+                        "band at {:?} has the same insets as the band above it", block_position);
+                assert!(self.floor > Au(0) || (band.left >= previous_band.left &&
+                                               band.right >= previous_band.right),
+                        "band at {:?} is excluded further than the band above it",
+                        block_position);
+            }
+            next_block_position = block_position + band.length;
+            previous_band = Some(band)
+        }
+        assert!(next_block_position == MAX_AU,
+                "the last band ends at {:?} rather than i32::MAX", next_block_position);
+    }
+
+    /// Returns an estimate of the number of bytes allocated on the heap by these exclusions.
+    pub fn approx_heap_bytes(&self) -> usize {
+        self.bands.approx_heap_bytes() +
+            self.excluded.capacity() * mem::size_of::<(Side, Size)>() +
+            self.full_width.capacity() * mem::size_of::<(Au, Au)>() +
+            self.shapes.capacity() * mem::size_of::<Shape>() +
+            self.intervals.capacity() * mem::size_of::<Interval>() +
+            self.spans.capacity() * mem::size_of::<Span>() +
+            self.shapes.iter().map(|shape| {
+                shape.2.capacity() * mem::size_of::<(Au, Au)>()
+            }).sum::<usize>()
+    }
+
+    /// Returns the distances from the left and right edges of the zone to the nearest excluded
+    /// area on each side at the given block position.
+    ///
+    /// The insets are physical, so they are not swapped in a right-to-left zone.
+    ///
+    /// A block position exactly on the boundary between two bands belongs to the lower band.
+    pub fn insets_at(&self, block_position: Au) -> (Au, Au) {
+        let band = self.band_at(block_position);
+        (-band.left, -band.right)
+    }
+
+    /// Returns the logical side that the exclusions at the given block position were placed on,
+    /// or `None` if nothing is excluded there or both sides are.
+    ///
+    /// A full-width exclusion counts as both sides. A block position exactly on the boundary
+    /// between two bands belongs to the lower band.
+    pub fn band_side(&self, block_position: Au) -> Option<Side> {
+        // The insets are kept per physical side, so they already record which sides contributed
+        // to a band. Only full-width exclusions, which are stored as insets on the inline-start
+        // side alone, need checking, and their list is searched whatever the direction.
+        if self.full_width.iter().any(|&(block_start, block_size)| {
+            block_position >= block_start &&
+                block_position < saturating_add(block_start, block_size)
+        }) {
+            return None
+        }
+        let band = self.band_at(block_position);
+        match (band.left < Au(0), band.right < Au(0)) {
+            (true, false) => Some(self.physical_side(Side::Left)),
+            (false, true) => Some(self.physical_side(Side::Right)),
+            (true, true) | (false, false) => None,
+        }
+    }
+
+    /// Returns the inline start and end of the region between the exclusions on either side at
+    /// the given block position.
+    ///
+    /// If the exclusions on either side meet or overlap, the region is empty and its start and
+    /// end are equal.
+    pub fn available_run(&self, block_position: Au) -> (Au, Au) {
+        let band = self.band_at(block_position);
+        let inline_start = self.inline_start - band.left;
+        (inline_start, cmp::max(self.inline_start + self.inline_size + band.right, inline_start))
+    }
+
+    /// Returns every run clear of exclusions at the given block position, from left to right.
+    ///
+    /// This is the run that `available_run` returns, split by the rectangles excluded with
+    /// `exclude_interval`. Empty runs are left out.
+    pub fn runs_at(&self, block_position: Au) -> Vec<(Au, Au)> {
+        let (inline_start, inline_end) = self.available_run(block_position);
+        let mut runs = vec![];
+        if inline_start < inline_end {
+            runs.push((inline_start, inline_end))
+        }
+        for &(block_start, block_end, interval_start, interval_end) in &self.intervals {
+            if block_position < block_start || block_position >= block_end {
+                continue
+            }
+            let (interval_start, interval_end) = (self.inline_start + interval_start,
+                                                  self.inline_start + interval_end);
+            runs = runs.into_iter().flat_map(|(run_start, run_end)| {
+                let before = (run_start, cmp::min(run_end, interval_start));
+                let after = (cmp::max(run_start, interval_end), run_end);
+                vec![before, after].into_iter().filter(|run| run.0 < run.1)
+            }).collect()
+        }
+        runs
+    }
+
+    /// Returns the inline size available to a box with the given block start and block size,
+    /// which is the least available inline size of all the bands that the box spans.
+    ///
+    /// This is cheaper than placing the box when its origin isn't needed. A box with zero block
+    /// size spans only the band at its block start. The result is never negative.
+    pub fn available_width(&self, block_start: Au, block_size: Au) -> Au {
+        let first_block_position = self.band_entry_at(block_start).0;
+        let block_end = cmp::max(saturating_add(block_start, block_size), block_start + Au(1));
+        let inline_size = self.inline_size;
+        let available_size = self.bands
+                                 .range(Bound::Included(first_block_position),
+                                        Bound::Excluded(block_end))
+                                 .map(|(_, band)| band.available_size(inline_size))
+                                 .min()
+                                 .unwrap_or(inline_size);
+        cmp::max(available_size, Au(0))
+    }
 
-impl Band {
-    fn new(left: Au, right: Au, length: Au) -> Band {
-        Band {
-            left: left,
-            right: right,
-            length: length,
+    /// Returns the widest available inline size among the bands that intersect the block range
+    /// from `block_lo` to `block_hi`, along with the point where that run starts.
+    ///
+    /// If several bands are equally wide, the topmost one wins. The block position of the point
+    /// is clamped to `block_lo`, and its inline position is physical, as in `available_run`. If
+    /// the range is empty, the result is the full inline size at `block_lo`.
+    pub fn widest_run_in(&self, block_lo: Au, block_hi: Au) -> (Au, Point) {
+        if block_lo >= block_hi {
+            return (self.inline_size, Point::new(self.inline_start, block_lo))
+        }
+        let first_block_position = self.band_entry_at(block_lo).0;
+        let mut widest = (Au(-1), Point::new(self.inline_start, block_lo));
+        for (&block_position, band) in self.bands.range(Bound::Included(first_block_position),
+                                                        Bound::Excluded(block_hi)) {
+            let available_size = cmp::max(band.available_size(self.inline_size), Au(0));
+            if available_size > widest.0 {
+                let origin = Point::new(self.inline_start - band.left,
+                                        cmp::max(block_position, block_lo));
+                widest = (available_size, origin)
+            }
         }
+        widest
     }
 
-    fn available_size(&self, inline_size: Au) -> Au {
-        inline_size + self.left + self.right
+    /// Returns the block start of the band boundary nearest to the given block position.
+    ///
+    /// The candidates are the start of the band containing the position and the start of the
+    /// band after it. A position halfway between the two snaps to the lesser one, and a
+    /// position in the final band always snaps to its start.
+    pub fn nearest_boundary(&self, block_position: Au) -> Au {
+        let &(block_start, ref band) = self.band_entry_at(block_position);
+        let block_end = block_start + band.length;
+        if block_end == MAX_AU || block_position - block_start <= block_end - block_position {
+            block_start
+        } else {
+            block_end
+        }
     }
 
-    fn get(&self, side: Side) -> Au {
-        match side {
-            Side::Left => self.left,
-            Side::Right => self.right,
+    /// Returns true if the given point lies within an excluded area.
+    ///
+    /// Excluded areas include their top and left edges but not their bottom and right edges, and
+    /// points outside the zone are never excluded.
+    pub fn is_excluded(&self, point: &Point) -> bool {
+        let inline_position = point.inline - self.inline_start;
+        if point.block < Au(0) || inline_position < Au(0) || inline_position >= self.inline_size {
+            return false
         }
+        let band = self.band_at(point.block);
+        inline_position < -band.left || inline_position >= self.inline_size + band.right ||
+            self.interval_end(inline_position, inline_position + Au(1), point.block,
+                              point.block + Au(1)).is_some()
     }
 
-    fn set(&mut self, side: Side, inline_size: Au) {
-        match side {
-            Side::Left => self.left = inline_size,
-            Side::Right => self.right = inline_size,
+    /// Returns true if a rectangle with the given origin and size would overlap an excluded area.
+    ///
+    /// Unlike `place`, this doesn't look for room for the rectangle; it only checks the spot
+    /// given. A rectangle with no area overlaps nothing.
+    pub fn would_overlap(&self, origin: &Point, size: &Size) -> bool {
+        if size.inline <= Au(0) || size.block <= Au(0) {
+            return false
         }
+        let inline_start = saturating_sub(origin.inline, self.inline_start);
+        let (inline_end, block_end) = (saturating_add(inline_start, size.inline),
+                                       saturating_add(origin.block, size.block));
+        if block_end <= Au(0) {
+            return false
+        }
+        let first_block_position = self.band_entry_at(origin.block).0;
+        let inline_size = self.inline_size;
+        let mut bands = self.bands.range(Bound::Included(first_block_position),
+                                         Bound::Excluded(block_end));
+        bands.any(|(_, band)| {
+            (inline_start < -band.left && inline_end > Au(0)) ||
+                (inline_end > inline_size + band.right && inline_start < inline_size)
+        }) || self.interval_end(inline_start, inline_end, origin.block, block_end).is_some()
     }
-}
 
-/// A logical point.
-#[derive(Clone, Copy, Debug)]
-pub struct Point {
-    /// The origin in the inline direction (normally horizontal).
-    pub inline: Au,
-    /// The origin in the block direction (normally vertical).
-    pub block: Au,
-}
+    /// Removes an area previously excluded with `exclude`, as though it had never been excluded.
+    ///
+    /// The area is given as the rectangle of the object that was placed, as returned by `place`,
+    /// and is extended to the top of the zone and to the edge on the given side the same way it
+    /// was when excluded. Returns false if no matching area was found.
+    ///
+    /// Areas excluded with `exclude_span`, including those that `translate_block` moved down away
+    /// from the top of the zone, are found the same way, by their side, inset, and block end.
+    ///
+    /// Because adjacent bands are merged as areas are excluded, the bands are rebuilt from the
+    /// remaining areas, which takes O(n) time in the number of exclusions.
+    pub fn remove(&mut self, side: Side, origin: &Point, size: &Size) -> bool {
+        let area = self.area_from_rect(side, origin, size);
+        if let Some(index) = self.excluded.iter().position(|&(excluded_side, ref excluded_size)| {
+            excluded_side == side && *excluded_size == area
+        }) {
+            self.excluded.remove(index);
+        } else if let Some(index) = self.spans.iter().position(|span| {
+            span.0 == side && span.1 == area.inline && span.2 <= origin.block &&
+                span.3 == area.block
+        }) {
+            self.spans.remove(index);
+        } else {
+            return false
+        }
+        self.rebuild();
+        true
+    }
 
-impl Point {
-    /// Creates a new logical point.
-    pub fn new(inline: Au, block: Au) -> Point {
-        Point {
-            inline: inline,
-            block: block,
+    /// Merges every run of adjacent bands with the same insets on both sides into a single band.
+    ///
+    /// Excluding areas already merges the bands it touches, so this only reduces the band count
+    /// of zones whose bands were built some other way, such as by deserialization. It never
+    /// changes where anything is placed. The band tree is also rebalanced, so that queries are
+    /// fast afterward. This takes O(n) time in the number of bands.
+    pub fn compact(&mut self) {
+        let mut previous_insets = None;
+        self.bands.retain(|_, band| {
+            let insets = Some((band.left, band.right));
+            let changed = insets != previous_insets;
+            previous_insets = insets;
+            changed
+        });
+        let mut next_block_positions: Vec<_> = self.bands.keys().skip(1).cloned().collect();
+        next_block_positions.push(MAX_AU);
+        for ((&block_position, band), next_block_position) in
+                self.bands.iter_mut().zip(next_block_positions) {
+            band.length = next_block_position - block_position
         }
+        self.bands.rebalance()
     }
-}
 
-/// A logical size.
-#[derive(Clone, Copy, Debug)]
-pub struct Size {
-    /// The size in the inline direction (normally horizontal).
-    pub inline: Au,
-    /// The size in the block direction (normally vertical).
-    pub block: Au,
-}
+    /// Rebalances the band tree and frees the spare capacity of the lists of excluded areas, which
+    /// can build up after many calls to `exclude` and `remove`.
+    ///
+    /// This is a maintenance operation that takes O(n) time in the number of bands and excluded
+    /// areas, so call it occasionally, such as after a relayout, rather than every frame. It never
+    /// changes where anything is placed.
+    pub fn shrink_to_fit(&mut self) {
+        self.bands.rebalance();
+        self.excluded.shrink_to_fit();
+        self.full_width.shrink_to_fit();
+        self.shapes.shrink_to_fit();
+        for shape in &mut self.shapes {
+            shape.2.shrink_to_fit()
+        }
+        self.intervals.shrink_to_fit();
+        self.spans.shrink_to_fit();
+        if let Some(ref mut undo) = self.undo {
+            undo.bands.shrink_to_fit()
+        }
+    }
 
-impl Size {
-    /// Creates a new logical size.
-    pub fn new(inline: Au, block: Au) -> Size {
-        Size {
-            inline: inline,
-            block: block,
+    /// Saves the current state of the zone, so that speculative changes can be rolled back with
+    /// `restore`.
+    ///
+    /// This copies every band and excluded area, taking O(n) time.
+    pub fn snapshot(&self) -> ExclusionsSnapshot {
+        ExclusionsSnapshot {
+            bands: self.bands.iter().map(|(&block_position, band)| {
+                (block_position, *band)
+            }).collect(),
+            inline_size: self.inline_size,
+            inline_start: self.inline_start,
+            direction: self.direction,
+            writing_mode: self.writing_mode,
+            max_block: self.max_block,
+            excluded: self.excluded.clone(),
+            full_width: self.full_width.clone(),
+            shapes: self.shapes.clone(),
+            intervals: self.intervals.clone(),
+            spans: self.spans.clone(),
+            floor: self.floor,
+            left_bottom: self.left_bottom,
+            right_bottom: self.right_bottom,
         }
     }
-}
 
-/// Where an object should be placed to avoid overlapping any excluded area.
-#[derive(Clone, Copy, Debug)]
-pub struct Placement {
-    /// The distance from the top left of the zone to the top left of the object.
-    pub origin: Point,
-    /// How much space is available for the object without overlapping any exclusions.
-    pub available_inline_size: Au,
-}
+    /// Returns the zone to the state saved in the given snapshot, including its inline size, inline
+    /// start, direction, and writing mode.
+    ///
+    /// Any exclusion since the snapshot can no longer be undone with `undo_last_exclude`.
+    pub fn restore(&mut self, snapshot: ExclusionsSnapshot) {
+        self.bands = snapshot.bands.into_iter().collect();
+        self.inline_size = snapshot.inline_size;
+        self.inline_start = snapshot.inline_start;
+        self.direction = snapshot.direction;
+        self.writing_mode = snapshot.writing_mode;
+        self.max_block = snapshot.max_block;
+        self.excluded = snapshot.excluded;
+        self.full_width = snapshot.full_width;
+        self.shapes = snapshot.shapes;
+        self.intervals = snapshot.intervals;
+        self.spans = snapshot.spans;
+        self.floor = snapshot.floor;
+        self.undo = None;
+        self.left_bottom = snapshot.left_bottom;
+        self.right_bottom = snapshot.right_bottom;
+    }
 
-impl Placement {
-    fn new(origin: &Point, available_inline_size: Au) -> Placement {
-        Placement {
-            origin: *origin,
-            available_inline_size: available_inline_size,
+    /// Removes all exclusions, returning the zone to the state it was in when created.
+    ///
+    /// The inline size of the zone is preserved, and so are the allocations of the lists of
+    /// excluded areas and of one band, so that the zone can be reused without allocating again
+    /// until it grows. The other bands are freed.
+    pub fn clear(&mut self) {
+        self.bands.clear_to(Au(0), Band::new(Au(0), Au(0), MAX_AU));
+        self.excluded.clear();
+        self.full_width.clear();
+        self.shapes.clear();
+        self.intervals.clear();
+        self.spans.clear();
+        self.floor = Au(0);
+        self.undo = None;
+        self.left_bottom = Au(0);
+        self.right_bottom = Au(0);
+    }
+
+    /// Replays all remaining excluded areas into a fresh set of bands.
+    fn rebuild(&mut self) {
+        let excluded = mem::take(&mut self.excluded);
+        let full_width = mem::take(&mut self.full_width);
+        let shapes = mem::take(&mut self.shapes);
+        let intervals = mem::take(&mut self.intervals);
+        let spans = mem::take(&mut self.spans);
+        self.clear();
+        self.intervals = intervals;
+        for &(side, ref size) in &excluded {
+            self.exclude(side, size)
+        }
+        for &(block_start, block_size) in &full_width {
+            self.exclude_full_width(block_start, block_size)
+        }
+        for &(side, ref origin, ref vertices) in &shapes {
+            self.exclude_shape(side, origin, vertices)
+        }
+        for &(side, inset, block_start, block_end) in &spans {
+            self.exclude_span(side, inset, block_start, block_end)
         }
+        self.undo = None;
     }
-}
 
-/// Left or right.
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub enum Side {
-    Left,
-    Right,
-}
+    /// Converts the rectangle of a placed object into the area, touching the top of the zone and
+    /// the edge on the given side, that `exclude` takes.
+    fn area_from_rect(&self, side: Side, origin: &Point, size: &Size) -> Size {
+        let inline_size = match self.physical_side(side) {
+            Side::Left => saturating_add(saturating_sub(origin.inline, self.inline_start),
+                                         size.inline),
+            Side::Right => saturating_sub(saturating_add(self.inline_start, self.inline_size),
+                                          origin.inline),
+        };
+        Size::new(inline_size, saturating_add(origin.block, size.block))
+    }
 
-impl Debug for Exclusions {
-    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
-        try!(writeln!(formatter, "Exclusions(inline_size={:?}): bands:", self.inline_size));
-        for (block_position, band) in self.bands.clone().into_iter() {
-            try!(writeln!(formatter, "    {:?} {:?}", block_position, band));
+    /// Places a box so that every band it spans is wide enough for it, as `place_block` does.
+    fn block_placement(&self, alignment: Side, size: &Size) -> Placement {
+        let mut min_block_position = Au(0);
+        loop {
+            let placement = self.place_at_or_below(alignment, size, min_block_position);
+            if size.inline > self.inline_size {
+                return placement
+            }
+            let block_start = placement.origin.block;
+            let first_block_position = self.band_entry_at(block_start).0;
+            let inline_size = self.inline_size;
+            let narrow_band_end =
+                self.bands
+                    .range(Bound::Included(first_block_position),
+                           Bound::Excluded(saturating_add(block_start, size.block)))
+                    .find(|&(_, band)| band.available_size(inline_size) < size.inline)
+                    .map(|(&block_position, band)| saturating_add(block_position, band.length));
+            match narrow_band_end {
+                Some(block_position) => min_block_position = block_position,
+                None => return placement,
+            }
         }
-        Ok(())
     }
-}
 
-impl Exclusions {
-    /// Creates a new rectangular exclusion zone infinitely long in the block direction with the
-    /// given inline size.
+    /// Places an object in the first band that is wide enough for it and that ends below the
+    /// given block position. The object is never placed above that position or the floor.
     ///
-    /// The zone starts out with no exclusions in it.
-    pub fn new(inline_size: Au) -> Exclusions {
-        Exclusions {
-            bands: iter::once((Au(0), Band::new(Au(0), Au(0), MAX_AU))).collect(),
-            inline_size: inline_size,
+    /// An object wider than the zone fits in no band, so it is placed below every exclusion.
+    fn place_at_or_below(&self, alignment: Side, size: &Size, min_block_position: Au)
+                         -> Placement {
+        let alignment = self.physical_side(alignment);
+        let inline_size = self.inline_size;
+        self.align_at_or_below(size, min_block_position, |band| {
+            match alignment {
+                Side::Left => -band.left,
+                Side::Right => inline_size + band.right - size.inline,
+            }
+        })
+    }
+
+    /// Places an object as `place_at_or_below` does, at the inline position within the band it
+    /// fits in that the given function returns, relative to the zone.
+    fn align_at_or_below<F>(&self, size: &Size, min_block_position: Au, inline_position: F)
+                            -> Placement
+                            where F: Fn(&Band) -> Au {
+        let mut min_block_position = self.min_block_for_width(size.inline, min_block_position);
+        loop {
+            let (block_position, band) = self.first_fit_at_or_below(size.inline,
+                                                                     min_block_position);
+            let inline_position = inline_position(band);
+
+            // If the object overlaps a rectangle in the middle of the zone, try again below it.
+            if let Some(interval_block_end) =
+                    self.interval_end(inline_position,
+                                      saturating_add(inline_position, size.inline),
+                                      block_position,
+                                      saturating_add(block_position, size.block)) {
+                min_block_position = interval_block_end;
+                continue
+            }
+
+            let origin = Point::new(self.inline_start + inline_position, block_position);
+            return Placement::new(&origin, band.available_size(self.inline_size))
         }
     }
 
-    /// Places an object so that it does not overlap any exclusions according to the CSS float
-    /// placement rules.
+    /// Returns the block position that an object with the given inline size may not be placed
+    /// above, given that it may not be placed above `min_block_position` either.
     ///
-    /// The object is aligned either to the left or right, depending on the size.
-    pub fn place(&mut self, alignment: Side, size: &Size) -> Placement {
-        let block_position =
+    /// An object wider than the zone fits in no band, so it must go below every exclusion.
+    fn min_block_for_width(&self, width: Au, min_block_position: Au) -> Au {
+        let min_block_position = cmp::max(min_block_position, self.floor);
+        if width > self.inline_size {
+            cmp::max(min_block_position, cmp::max(self.left_bottom, self.right_bottom))
+        } else {
+            min_block_position
+        }
+    }
+
+    /// Finds the first band that is at least the given width and that ends below the given block
+    /// position, and returns it along with the highest block position within it at or below that
+    /// position.
+    fn first_fit_at_or_below(&self, width: Au, min_block_position: Au) -> (Au, &Band) {
+        let inline_size = self.inline_size;
+        let &(band_block_position, ref band) =
             self.bands
                 .lower_bound_with(|&band_block_start, band| {
-                    compare_inline_size(band_block_start, band, size, self.inline_size)
-                }).expect("Exclusions::place(): Didn't find a band!").0;
-        let band = self.bands.get(&block_position).unwrap();
-        let inline_position = match alignment {
-            Side::Left => -band.left,
-            Side::Right => self.inline_size + band.right - size.inline,
+                    let band_block_end = band_block_start + band.length;
+                    if band_block_end <= min_block_position && band_block_end != MAX_AU {
+                        Ordering::Greater
+                    } else {
+                        compare_inline_size(band_block_start, band, width, inline_size)
+                    }
+                }).expect("Exclusions::place(): Didn't find a band!");
+        (cmp::max(band_block_position, min_block_position), band)
+    }
+
+    /// Returns the given placement of an object of the given size, unless the object would end
+    /// past the limit set with `set_max_block` or start at or end below a barrier.
+    fn within_limits(&self, placement: Placement, size: &Size) -> Option<Placement> {
+        let block_end = saturating_add(placement.origin.block, size.block);
+        match self.max_block {
+            Some(max_block) if block_end > max_block => return None,
+            Some(_) | None => {}
+        }
+        match self.barrier() {
+            Some(barrier) if placement.origin.block >= barrier || block_end > barrier => None,
+            Some(_) | None => Some(placement),
+        }
+    }
+
+    /// Returns the least block end of the rectangles excluded with `exclude_interval` that
+    /// overlap the given rectangle, if any, with positions relative to the zone.
+    fn interval_end(&self, inline_start: Au, inline_end: Au, block_start: Au, block_end: Au)
+                    -> Option<Au> {
+        if inline_end <= inline_start || block_end <= block_start {
+            return None
+        }
+        self.intervals.iter().filter(|interval| {
+            block_start < interval.1 && block_end > interval.0 && inline_start < interval.3 &&
+                inline_end > interval.2
+        }).map(|interval| interval.1).min()
+    }
+
+    /// Converts a side relative to the inline base direction into a physical side.
+    fn physical_side(&self, side: Side) -> Side {
+        match (self.direction, side) {
+            (Direction::Ltr, side) => side,
+            (Direction::Rtl, Side::Left) => Side::Right,
+            (Direction::Rtl, Side::Right) => Side::Left,
+        }
+    }
+
+    /// Records that an area excluded on the given physical side reaches down to the given block
+    /// position.
+    fn extend_bottom(&mut self, side: Side, block_position: Au) {
+        match side {
+            Side::Left => self.left_bottom = cmp::max(self.left_bottom, block_position),
+            Side::Right => self.right_bottom = cmp::max(self.right_bottom, block_position),
+        }
+    }
+
+    /// Returns the band spanning the given block position.
+    ///
+    /// A block position exactly on the boundary between two bands belongs to the lower band, and
+    /// positions past the end of the last finite band belong to the infinitely long final band.
+    /// The band tree is not splayed.
+    pub fn band_at(&self, block_position: Au) -> &Band {
+        &self.band_entry_at(block_position).1
+    }
+
+    /// Returns the band spanning the given block position, along with its block start, as
+    /// `band_at` does.
+    fn band_entry_at(&self, block_position: Au) -> &(Au, Band) {
+        self.bands.lower_bound_with(|&band_block_position, band| {
+            let band_block_end = band_block_position + band.length;
+            if block_position >= band_block_end && band_block_end != MAX_AU {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }
+        }).expect("Exclusions::band_at(): Didn't find a band!")
+    }
+
+    /// Saves the bands that excluding the given inline size from the given physical side down to
+    /// the given block position will change, so that the exclusion can be undone.
+    ///
+    /// This visits the same bands that `exclude_range` does: those from the band containing the
+    /// bottom of the area up to the first band already excluded at least as far, or to the top.
+    fn save_undo(&mut self, side: Side, inset: Au, block_end: Au) {
+        let block_end = cmp::min(block_end, MAX_AU - Au(1));
+        let (mut region_start, region_end) = {
+            let &(block_position, ref band) = self.band_entry_at(block_end);
+            (block_position, saturating_add(block_position, band.length))
         };
-        let origin = Point::new(inline_position, block_position);
-        Placement::new(&origin, band.available_size(self.inline_size))
+        while region_start > Au(0) {
+            let &(block_position, ref band) = self.band_entry_at(region_start - Au(1));
+            region_start = block_position;
+            if self.floor == Au(0) && -band.get(side) > inset {
+                break
+            }
+        }
+        let bands = self.bands
+                        .range(Bound::Included(region_start), Bound::Excluded(region_end))
+                        .map(|(&block_position, band)| (block_position, *band))
+                        .collect();
+        self.undo = Some(Undo {
+            bands,
+            region_end,
+            left_bottom: self.left_bottom,
+            right_bottom: self.right_bottom,
+        })
     }
 
-    /// Excludes a rectangular area of the given size, preventing any objects from being placed
-    /// within it.
+    /// Excludes the given inline size from the given physical side of every band between the
+    /// given block positions, splitting the bands at both ends and merging any that become equal.
+    /// The bands just outside the range are merged too, so the bands that result don't depend on
+    /// the order in which areas are excluded.
     ///
-    /// The excluded area touches the top left or top right of the zone, depending on the side.
-    pub fn exclude(&mut self, side: Side, size: &Size) {
-        if size.inline == Au(0) || size.block == Au(0) {
+    /// The range is clipped to the zone, stopping short of the maximum block position so that the
+    /// final, infinitely long band always remains.
+    fn exclude_range(&mut self, side: Side, inset: Au, block_start: Au, block_end: Au) {
+        let block_start = cmp::max(block_start, Au(0));
+        let block_end = cmp::min(block_end, MAX_AU - Au(1));
+        if block_end <= block_start {
             return
         }
 
-        self.split(size.block);
+        // Above the floor, a band may be wider than the one below it, so a wider band doesn't
+        // mean that the rest of the range is already excluded.
+        let narrowing = self.floor == Au(0);
+        if block_start > Au(0) {
+            self.split(block_start)
+        }
+        self.split(block_end);
 
-        let (mut last_block_position, mut last_band): (Au, Option<Band>) = (size.block, None);
-        loop {
+        let (mut last_block_position, mut last_band): (Au, Option<Band>) = (block_end, None);
+        while last_block_position > block_start {
             let mut band_to_delete = None;
             match self.bands.get_with_mut(|block_position, band| {
                 if last_block_position <= *block_position {
@@ -189,18 +2403,22 @@ impl Exclusions {
                     Ordering::Equal
                 }
             }) {
-                Some(&mut (block_position, ref mut band)) if -band.get(side) <= size.inline => {
-                    // Extend this band.
-                    //
-                    //  ┌────────────────┐
-                    //  │                │
-                    //  ├───────┬────┬───┘
-                    //  │       │ ─→ ┆
-                    //  ├───┬───┴┄┄┄┄┘
-                    //  │   │
-                    //  ├───┘
-                    //  │
-                    band.set(side, -size.inline);
+                Some(&mut (block_position, ref mut band)) => {
+                    if -band.get(side) <= inset {
+                        // Extend this band.
+                        //
+                        //  ┌────────────────┐
+                        //  │                │
+                        //  ├───────┬────┬───┘
+                        //  │       │ ─→ ┆
+                        //  ├───┬───┴┄┄┄┄┘
+                        //  │   │
+                        //  ├───┘
+                        //  │
+                        band.set(side, -inset);
+                    } else if narrowing {
+                        break
+                    }
 
                     // Merge with the next band if we can.
                     //
@@ -216,7 +2434,7 @@ impl Exclusions {
                     //  │
                     if let Some(ref last_band) = last_band {
                         if band.left == last_band.left && band.right == last_band.right {
-                            band.length = band.length + last_band.length;
+                            band.length = saturating_add(band.length, last_band.length);
                             band_to_delete = Some(last_block_position)
                         }
                     }
@@ -224,7 +2442,7 @@ impl Exclusions {
                     last_block_position = block_position;
                     last_band = Some(*band);
                 }
-                Some(_) | None => break,
+                None => break,
             }
 
             // Delete the old band if we merged bands above.
@@ -243,6 +2461,35 @@ impl Exclusions {
                 self.bands.remove(&band_to_delete);
             }
         }
+
+        // The bands just outside the range may now match the ones just inside.
+        self.merge_at(block_end);
+        self.merge_at(block_start);
+    }
+
+    /// Merges the band starting at the given block position into the band above it if they have
+    /// the same insets.
+    fn merge_at(&mut self, block_position: Au) {
+        let lower_band = match self.bands.get(&block_position) {
+            Some(band) if block_position > Au(0) => *band,
+            Some(_) | None => return,
+        };
+        match self.bands.get_with_mut(|&band_block_position, band| {
+            if block_position <= band_block_position {
+                Ordering::Less
+            } else if block_position > band_block_position + band.length {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }) {
+            Some(&mut (_, ref mut upper_band)) if upper_band.left == lower_band.left &&
+                    upper_band.right == lower_band.right => {
+                upper_band.length = saturating_add(upper_band.length, lower_band.length)
+            }
+            Some(_) | None => return,
+        }
+        self.bands.remove(&block_position);
     }
 
     /// Splits the band spanning the given block position in two at that point.
@@ -271,25 +2518,347 @@ impl Exclusions {
                     }
                 }).expect("Exclusions::split(): Didn't find band to split!");
             floor = upper_block_position + upper_band.length;
-            upper_band.length = block_position - upper_block_position;
+            upper_band.length = saturating_sub(block_position, upper_block_position);
             left_size = upper_band.left;
             right_size = upper_band.right
         }
-        let lower_band = Band::new(left_size, right_size, floor - block_position);
+        let lower_band = Band::new(left_size, right_size, saturating_sub(floor, block_position));
         self.bands.insert(block_position, lower_band);
     }
 }
 
-fn compare_inline_size(band_block_start: Au,
-                       band: &Band,
-                       exclusion_size: &Size,
-                       inline_size: Au)
+/// Orders a band relative to the first band wide enough for an object of the given width.
+///
+/// The final band always compares equal if no earlier band is wide enough, even if it is too
+/// narrow itself, as it is in a zone with zero inline size, so that a band is always found.
+fn compare_inline_size(band_block_start: Au, band: &Band, width: Au, inline_size: Au)
                        -> Ordering {
-    match exclusion_size.inline.cmp(&band.available_size(inline_size)) {
+    match width.cmp(&band.available_size(inline_size)) {
         Ordering::Less | Ordering::Equal => Ordering::Less,
         Ordering::Greater if band_block_start + band.length == MAX_AU => Ordering::Equal,
         Ordering::Greater => Ordering::Greater,
     }
 }
 
+/// Appends a point to an outline, dropping it if it duplicates the last point and replacing the
+/// last point if it lies on the same line.
+fn push_outline_point(outline: &mut Vec<Point>, point: Point) {
+    if outline.last() == Some(&point) {
+        return
+    }
+    if outline.len() >= 2 {
+        let (first, second) = (outline[outline.len() - 2], outline[outline.len() - 1]);
+        if (first.inline == second.inline && second.inline == point.inline) ||
+                (first.block == second.block && second.block == point.block) {
+            outline.pop();
+        }
+    }
+    outline.push(point)
+}
+
+/// Appends an SVG `<rect>` element with the given position and size to an SVG document.
+fn push_svg_rect(svg: &mut String, inline: Au, block: Au, inline_size: Au, block_size: Au) {
+    svg.push_str(&format!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+                          inline.0,
+                          block.0,
+                          inline_size.0,
+                          block_size.0))
+}
+
+/// Returns the block start and end of the polygon with the given vertices relative to the given
+/// origin, or `None` if it has no area.
+fn shape_block_range(origin: &Point, vertices: &[(Au, Au)]) -> Option<(Au, Au)> {
+    let block_start = vertices.iter().map(|vertex| vertex.1).min();
+    let block_end = vertices.iter().map(|vertex| vertex.1).max();
+    match (block_start, block_end) {
+        (Some(block_start), Some(block_end)) if vertices.len() >= 3 && block_start < block_end => {
+            Some((origin.block + block_start, origin.block + block_end))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the least and greatest inline positions of the polygon with the given vertices
+/// between the given block positions, inclusive.
+///
+/// No vertex may lie strictly between the block positions, so that the extremes are found where
+/// the edges cross them.
+fn shape_inline_extent(vertices: &[(Au, Au)], block_start: Au, block_end: Au) -> (Au, Au) {
+    let (mut inline_start, mut inline_end) = (MAX_AU, -MAX_AU);
+    for (index, &(start_inline, start_block)) in vertices.iter().enumerate() {
+        let (end_inline, end_block) = vertices[(index + 1) % vertices.len()];
+        let clipped_start = cmp::max(cmp::min(start_block, end_block), block_start);
+        let clipped_end = cmp::min(cmp::max(start_block, end_block), block_end);
+        if clipped_start > clipped_end {
+            continue
+        }
+        let inline_positions = if start_block == end_block {
+            [start_inline, end_inline]
+        } else {
+            // Interpolate in 64 bits to avoid overflow.
+            let interpolate = |block_position: Au| {
+                let offset = (block_position - start_block).0 as i64;
+                let inline_delta = (end_inline - start_inline).0 as i64;
+                let block_delta = (end_block - start_block).0 as i64;
+                start_inline + Au((inline_delta * offset / block_delta) as i32)
+            };
+            [interpolate(clipped_start), interpolate(clipped_end)]
+        };
+        for &inline_position in &inline_positions {
+            inline_start = cmp::min(inline_start, inline_position);
+            inline_end = cmp::max(inline_end, inline_position);
+        }
+    }
+    (inline_start, inline_end)
+}
+
+/// Adds two lengths, clamping to the representable range instead of wrapping around.
+fn saturating_add(a: Au, b: Au) -> Au {
+    Au(a.0.saturating_add(b.0))
+}
+
+/// Subtracts two lengths, clamping to the representable range instead of wrapping around.
+fn saturating_sub(a: Au, b: Au) -> Au {
+    Au(a.0.saturating_sub(b.0))
+}
+
+/// Returns true if the given bands start at zero, are contiguous and nonempty, end with an
+/// infinitely long band, and have insets within a zone of the given inline size.
+fn bands_are_valid(bands: &[(Au, Band)], inline_size: Au) -> bool {
+    let mut next_block_position = Au(0);
+    for &(block_position, ref band) in bands {
+        if block_position != next_block_position || next_block_position == MAX_AU ||
+                band.length <= Au(0) || band.length > MAX_AU - block_position {
+            return false
+        }
+        for &inset in &[-band.left, -band.right] {
+            if inset < Au(0) || inset > inline_size {
+                return false
+            }
+        }
+        next_block_position = block_position + band.length
+    }
+    next_block_position == MAX_AU
+}
+
+/// The fields of a zone, in the order in which they are serialized.
+#[cfg(feature = "serde")]
+type SerializedExclusions = (Au,
+                             Direction,
+                             Vec<(Au, Band)>,
+                             Vec<(Side, Size)>,
+                             Vec<(Au, Au)>,
+                             Vec<Shape>,
+                             Au,
+                             Vec<Interval>,
+                             Vec<Span>,
+                             Vec<Au>,
+                             WritingMode);
+
+#[cfg(feature = "serde")]
+impl Serialize for Exclusions {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        let bands: Vec<_> = self.bands.iter().map(|(&block_position, band)| {
+            (block_position, *band)
+        }).collect();
+        (self.inline_size,
+         self.direction,
+         bands,
+         &self.excluded,
+         &self.full_width,
+         &self.shapes,
+         self.inline_start,
+         &self.intervals,
+         &self.spans,
+         // Written as a list of at most one size, so that no size stands in for `None`.
+         self.max_block.iter().cloned().collect::<Vec<_>>(),
+         self.writing_mode).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Exclusions {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Exclusions, D::Error> {
+        let (inline_size,
+             direction,
+             bands,
+             excluded,
+             full_width,
+             shapes,
+             inline_start,
+             intervals,
+             spans,
+             max_block,
+             writing_mode): SerializedExclusions = Deserialize::deserialize(deserializer)?;
+        if inline_size < Au(0) {
+            return Err(de::Error::invalid_value("inline size must not be negative"))
+        }
+        if !bands_are_valid(&bands, inline_size) {
+            return Err(de::Error::invalid_value("bands must be contiguous, start at zero, and \
+                                                 have insets within the zone"))
+        }
+        if max_block.len() > 1 {
+            return Err(de::Error::invalid_value("there must be at most one block size limit"))
+        }
+        let mut exclusions = Exclusions::with_direction(inline_size, direction);
+        exclusions.inline_start = inline_start;
+        exclusions.writing_mode = writing_mode;
+        exclusions.max_block = max_block.first().cloned();
+        exclusions.bands = bands.into_iter().collect();
+        for &(side, ref size) in &excluded {
+            let side = exclusions.physical_side(side);
+            exclusions.extend_bottom(side, size.block)
+        }
+        exclusions.excluded = excluded;
+        for &(block_start, block_size) in &full_width {
+            exclusions.floor = cmp::max(exclusions.floor, saturating_add(block_start, block_size))
+        }
+        exclusions.full_width = full_width;
+        for &(side, ref origin, ref vertices) in &shapes {
+            if let Some((_, block_end)) = shape_block_range(origin, vertices) {
+                let side = exclusions.physical_side(side);
+                exclusions.extend_bottom(side, block_end);
+                exclusions.floor = cmp::max(exclusions.floor, block_end)
+            }
+        }
+        exclusions.shapes = shapes;
+        exclusions.intervals = intervals;
+        for &(side, _, block_start, block_end) in &spans {
+            let side = exclusions.physical_side(side);
+            exclusions.extend_bottom(side, block_end);
+            exclusions.floor = cmp::max(exclusions.floor, block_start)
+        }
+        exclusions.spans = spans;
+        Ok(exclusions)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Band {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        (self.left, self.right, self.length).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Band {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Band, D::Error> {
+        let (left, right, length) = Deserialize::deserialize(deserializer)?;
+        Ok(Band::new(left, right, length))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Point {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        (self.inline, self.block).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Point {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Point, D::Error> {
+        let (inline, block) = Deserialize::deserialize(deserializer)?;
+        Ok(Point::new(inline, block))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Size {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        (self.inline, self.block).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Size {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Size, D::Error> {
+        let (inline, block) = Deserialize::deserialize(deserializer)?;
+        Ok(Size::new(inline, block))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Placement {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        (self.origin, self.available_inline_size).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Placement {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Placement, D::Error> {
+        let (origin, available_inline_size) = Deserialize::deserialize(deserializer)?;
+        Ok(Placement::new(&origin, available_inline_size))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Side {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        serializer.serialize_str(match *self {
+            Side::Left => "left",
+            Side::Right => "right",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Side {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Side, D::Error> {
+        let side: String = Deserialize::deserialize(deserializer)?;
+        match &*side {
+            "left" => Ok(Side::Left),
+            "right" => Ok(Side::Right),
+            _ => Err(de::Error::unknown_variant(&side)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Direction {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        serializer.serialize_str(match *self {
+            Direction::Ltr => "ltr",
+            Direction::Rtl => "rtl",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Deserialize for Direction {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Direction, D::Error> {
+        let direction: String = Deserialize::deserialize(deserializer)?;
+        match &*direction {
+            "ltr" => Ok(Direction::Ltr),
+            "rtl" => Ok(Direction::Rtl),
+            _ => Err(de::Error::unknown_variant(&direction)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for WritingMode {
+    fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        serializer.serialize_str(match *self {
+            WritingMode::HorizontalTb => "horizontal-tb",
+            WritingMode::VerticalRl => "vertical-rl",
+            WritingMode::VerticalLr => "vertical-lr",
+            WritingMode::SidewaysLr => "sideways-lr",
+        })
+    }
+}
 
+#[cfg(feature = "serde")]
+impl Deserialize for WritingMode {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<WritingMode, D::Error> {
+        let writing_mode: String = Deserialize::deserialize(deserializer)?;
+        match &*writing_mode {
+            "horizontal-tb" => Ok(WritingMode::HorizontalTb),
+            "vertical-rl" => Ok(WritingMode::VerticalRl),
+            "vertical-lr" => Ok(WritingMode::VerticalLr),
+            "sideways-lr" => Ok(WritingMode::SidewaysLr),
+            _ => Err(de::Error::unknown_variant(&writing_mode)),
+        }
+    }
+}
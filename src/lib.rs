@@ -14,22 +14,34 @@
 //! In practice, this algorithm seems to be O(n) for placement of n floats due to the splay tree
 //! and aggressive merging of bands. Worst case, it is O(n²); however, this is very rare.
 
-#![cfg_attr(test, feature(test))]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(all(test, feature = "std"), feature(test))]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
 extern crate app_units;
-#[cfg(test)]
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "serde")]
+#[cfg_attr(test, macro_use)]
+extern crate serde;
+#[cfg(all(test, feature = "std"))]
 #[macro_use]
 extern crate quickcheck;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 extern crate rand;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 extern crate test as rust_test;
 
+pub mod columns;
 pub mod exclusions;
-mod map;
+pub mod map;
 mod node;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod bench;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test;
+#[cfg(all(test, not(feature = "std")))]
+mod test_no_std;
 
@@ -8,29 +8,133 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::borrow::Borrow;
-use std::cell::UnsafeCell;
-use std::cmp::Ordering::{self, Less, Equal, Greater};
-use std::default::Default;
-use std::iter::{FromIterator, IntoIterator};
-use std::mem;
-use std::ops::{Index, IndexMut};
+//! An ordered map backed by a splay tree.
+//!
+//! Splay trees move every key that is looked up or inserted to the root, so
+//! keys that are accessed repeatedly, or that are close to recently accessed
+//! keys, are cheap to find again. This suits the float placement algorithm in
+//! this crate, which mostly touches the bands next to the most recent float,
+//! but the map is useful in its own right.
+//!
+//! ```
+//! use buoyancy::map::SplayMap;
+//!
+//! let mut map = SplayMap::new();
+//! map.insert(3, "c");
+//! map.insert(1, "a");
+//! map.insert(2, "b");
+//!
+//! assert_eq!(map.get(&2), Some(&"b"));
+//! assert_eq!(map.get(&4), None);
+//! assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::UnsafeCell;
+use core::cmp;
+use core::cmp::Ordering::{self, Less, Equal, Greater};
+use core::default::Default;
+#[cfg(all(test, feature = "std"))]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(all(test, feature = "std"))]
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, IntoIterator};
+use core::mem;
+use core::ops::{Bound, Index, IndexMut};
 
 use super::node::Node;
 
+/// An ordered map backed by a splay tree.
+///
 /// The implementation of this splay tree is largely based on the c code at:
 ///     ftp://ftp.cs.cmu.edu/usr/ftp/usr/sleator/splaying/top-down-splay.c
 /// This version of splaying is a top-down splay operation.
+///
+/// Lookups through `&self`, such as `get` and the iterators, never splay, so
+/// they may be freely interleaved. Operations that take `&mut self` splay the
+/// key they touch to the root.
 pub struct SplayMap<K: Ord, V> {
     root: UnsafeCell<Option<Box<Node<K, V>>>>,
     size: usize,
 }
 
+/// An in-order iterator that moves the entries out of a map.
 pub struct IntoIter<K, V> {
     cur: Option<Box<Node<K, V>>>,
     remaining: usize,
 }
 
+/// A reverse-order iterator over references to the entries of a map.
+pub struct IterRev<'a, K: 'a, V: 'a> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+/// A node waiting to be visited by `IterMut`: its key, its value, and its
+/// right subtree, which is visited after it.
+type PendingMut<'a, K, V> = (&'a K, &'a mut V, &'a mut Option<Box<Node<K, V>>>);
+
+/// An in-order iterator over mutable references to the values of a map,
+/// alongside their keys.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    stack: Vec<PendingMut<'a, K, V>>,
+    remaining: usize,
+}
+
+/// An in-order iterator over the keys of a map.
+pub struct Keys<'a, K: 'a, V: 'a> {
+    iter: Iter<'a, K, V>,
+}
+
+/// An in-order iterator over the values of a map.
+pub struct Values<'a, K: 'a, V: 'a> {
+    iter: Iter<'a, K, V>,
+}
+
+/// An in-order iterator over mutable references to the values of a map.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    iter: IterMut<'a, K, V>,
+}
+
+/// An in-order iterator over references to the entries of a map whose keys
+/// lie within a range.
+pub struct Range<'a, K: 'a, V: 'a> {
+    stack: Vec<&'a Node<K, V>>,
+    upper: Bound<K>,
+}
+
+/// A view into a single entry in a map, which may either be vacant or
+/// occupied.
+pub enum Entry<'a, K: Ord + 'a, V: 'a> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// An entry for a key that is present in the map. The key is at the root.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a> {
+    key_value: &'a mut (K, V),
+}
+
+/// An entry for a key that is not present in the map. The closest key is at
+/// the root.
+pub struct VacantEntry<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut SplayMap<K, V>,
+    key: K,
+}
+
+/// An in-order iterator over references to the entries of a map.
+///
+/// Nodes have no parent links, so the path from the root to the next node to
+/// be visited is kept on an explicit stack.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
 /// Performs a top-down splay operation on a tree rooted at `node`. This will
 /// modify the pointer to contain the new root of the tree once the splay
 /// operation is done. When finished, if `key` is in the tree, it will be at the
@@ -108,95 +212,306 @@ fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>)
     mem::forget(mem::replace(&mut node.right, newleft));
 }
 
-fn splay_with_key<K, V, Q: ?Sized>(key: &Q, node: &mut Box<Node<K, V>>)
-                                   where K: Ord + Borrow<Q>, Q: Ord {
+/// Builds a perfectly balanced tree from the next `count` entries of an
+/// in-order iterator. The recursion is only as deep as the tree being built.
+fn build_balanced<K, V, I>(entries: &mut I, count: usize) -> Option<Box<Node<K, V>>>
+                           where I: Iterator<Item = (K, V)> {
+    if count == 0 {
+        return None
+    }
+    let left = build_balanced(entries, count / 2);
+    let (key, value) = entries.next().expect("build_balanced(): Ran out of entries!");
+    let right = build_balanced(entries, count - count / 2 - 1);
+    Some(Node::new(key, value, left, right))
+}
+
+/// Counts the nodes in a tree without recursing.
+fn count_nodes<K, V>(node: &Option<Box<Node<K, V>>>) -> usize {
+    let mut count = 0;
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if let Some(ref node) = *node {
+            count += 1;
+            stack.push(&node.left);
+            stack.push(&node.right);
+        }
+    }
+    count
+}
+
+fn splay_with_key<K, V, Q>(key: &Q, node: &mut Box<Node<K, V>>)
+                           where K: Ord + Borrow<Q>, Q: ?Sized + Ord {
     splay_with(|other_key, _| key.cmp(other_key.borrow()), node)
 }
 
-fn lower_bound_with<K, V, Q>(mut compare: Q, node: &Box<Node<K, V>>) -> Option<&(K, V)>
+fn lower_bound_with<K, V, Q>(mut compare: Q, mut node: &Node<K, V>) -> Option<&(K, V)>
                              where K: Ord, Q: FnMut(&K, &V) -> Ordering {
-    match compare(&node.key_value.0, &node.key_value.1) {
-        Less => {
-            if let Some(ref left) = node.left {
-                if let Some(key_value) = lower_bound_with(compare, left) {
-                    return Some(key_value)
-                }
+    let mut found = None;
+    loop {
+        let next = match compare(&node.key_value.0, &node.key_value.1) {
+            Less => {
+                found = Some(&node.key_value);
+                &node.left
             }
-            Some(&node.key_value)
+            Greater => &node.right,
+            Equal => return Some(&node.key_value),
+        };
+        match *next {
+            Some(ref next) => node = next,
+            None => return found,
         }
-        Greater => {
-            match node.right {
-                Some(ref right) => lower_bound_with(compare, right),
-                None => None,
+    }
+}
+
+fn upper_bound_with<K, V, Q>(mut compare: Q, mut node: &Node<K, V>) -> Option<&(K, V)>
+                             where K: Ord, Q: FnMut(&K, &V) -> Ordering {
+    let mut found = None;
+    loop {
+        let next = match compare(&node.key_value.0, &node.key_value.1) {
+            Greater => {
+                found = Some(&node.key_value);
+                &node.right
             }
+            Less => &node.left,
+            Equal => return Some(&node.key_value),
+        };
+        match *next {
+            Some(ref next) => node = next,
+            None => return found,
         }
-        Equal => Some(&node.key_value),
     }
 }
 
 impl<K: Ord, V> SplayMap<K, V> {
+    /// Creates a new, empty map.
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let mut map = SplayMap::new();
+    /// map.insert("key", 1);
+    /// assert_eq!(map.len(), 1);
+    /// ```
     pub fn new() -> SplayMap<K, V> {
         SplayMap { root: UnsafeCell::new(None), size: 0 }
     }
 
-    /// Moves all values out of this map, transferring ownership to the given
-    /// iterator.
-    pub fn into_iter(mut self) -> IntoIter<K, V> {
-        IntoIter { cur: self.root_mut().take(), remaining: self.size }
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns true if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns an estimate of the number of bytes allocated on the heap for
+    /// the nodes of this map. This does not include any heap allocations owned
+    /// by the keys or values themselves.
+    pub fn approx_heap_bytes(&self) -> usize {
+        self.size * mem::size_of::<Node<K, V>>()
+    }
+
+    /// Returns an iterator over references to the entries of this map in
+    /// ascending key order. The tree is not splayed.
+    pub fn iter<'a>(&'a self) -> Iter<'a, K, V> {
+        let mut iter = Iter { stack: vec![], remaining: self.size };
+        iter.push_left_spine(self.root_ref());
+        iter
+    }
+
+    /// Returns an iterator over references to the entries of this map in
+    /// descending key order. The tree is not splayed.
+    pub fn iter_rev<'a>(&'a self) -> IterRev<'a, K, V> {
+        let mut iter = IterRev { stack: vec![], remaining: self.size };
+        iter.push_right_spine(self.root_ref());
+        iter
+    }
+
+    /// Returns an iterator over references to the entries of this map in
+    /// ascending key order that allows the values to be modified. The tree is
+    /// not splayed.
+    pub fn iter_mut<'a>(&'a mut self) -> IterMut<'a, K, V> {
+        let mut iter = IterMut { stack: vec![], remaining: self.size };
+        iter.push_left_spine(self.root_mut());
+        iter
+    }
+
+    /// Returns an iterator over the keys of this map in ascending order.
+    pub fn keys<'a>(&'a self) -> Keys<'a, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Returns an iterator over the values of this map in ascending key order.
+    pub fn values<'a>(&'a self) -> Values<'a, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Returns an iterator over mutable references to the values of this map
+    /// in ascending key order.
+    pub fn values_mut<'a>(&'a mut self) -> ValuesMut<'a, K, V> {
+        ValuesMut { iter: self.iter_mut() }
+    }
+
+    /// Returns an iterator over references to the entries of this map whose
+    /// keys lie between the given bounds, in ascending key order. The tree is
+    /// not splayed.
+    pub fn range<'a>(&'a self, lower: Bound<K>, upper: Bound<K>) -> Range<'a, K, V> {
+        // Seed the stack with the path to the first key within the lower
+        // bound, keeping only the nodes that are themselves within it.
+        let mut stack = vec![];
+        let mut node = self.root_ref();
+        while let Some(ref current) = *node {
+            let within_lower = match lower {
+                Bound::Included(ref lower) => current.key_value.0 >= *lower,
+                Bound::Excluded(ref lower) => current.key_value.0 > *lower,
+                Bound::Unbounded => true,
+            };
+            if within_lower {
+                stack.push(&**current);
+                node = &current.left;
+            } else {
+                node = &current.right;
+            }
+        }
+        Range { stack, upper }
     }
 
     /// Clears the tree in O(1) extra space (including the stack). This is
     /// necessary to prevent stack exhaustion with extremely large trees.
     pub fn clear(&mut self) {
-        let iter = IntoIter {
+        // Dropping the iterator frees the nodes one at a time.
+        drop(IntoIter {
             cur: self.root_mut().take(),
             remaining: self.size,
-        };
-        for _ in iter {
-            // ignore, drop the values (and the node)
-        }
+        });
         self.size = 0;
     }
 
+    /// Removes every entry and then inserts the given one, as `clear` followed by `insert` would,
+    /// but reuses the allocation of the root node rather than freeing it and allocating another.
+    /// Like `clear`, this frees the other nodes in O(1) extra space.
+    pub fn clear_to(&mut self, key: K, value: V) {
+        let size = self.size;
+        match *self.root_mut() {
+            Some(ref mut root) => {
+                // Both subtrees together hold fewer than `size` nodes, so neither iterator can
+                // count below zero.
+                drop(IntoIter { cur: root.pop_left(), remaining: size });
+                drop(IntoIter { cur: root.pop_right(), remaining: size });
+                root.key_value = (key, value);
+            }
+            ref mut root => *root = Some(Node::new(key, value, None, None)),
+        }
+        self.size = 1;
+    }
+
+    /// Removes every entry for which the predicate returns false, keeping the
+    /// rest in order.
+    ///
+    /// The entries are moved out of the tree one at a time and the ones kept
+    /// are reinserted in ascending order, which takes O(n) time and leaves the
+    /// tree as a single spine with the greatest key at the root.
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let mut map: SplayMap<_, _> = (0..8).map(|i| (i, i * 10)).collect();
+    /// map.retain(|&key, value| { *value += 1; key % 3 == 0 });
+    /// assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(0, 1), (3, 31), (6, 61)]);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        let entries = mem::take(self).into_iter();
+        for (key, mut value) in entries {
+            if f(&key, &mut value) {
+                // Every key kept so far is less than this one, so the root is
+                // the closest key to it.
+                self.insert_at_root(key, value);
+            }
+        }
+    }
+
+    /// Rebuilds the tree so that it is perfectly balanced, without changing
+    /// its contents.
+    ///
+    /// Splaying keeps the amortized cost of operations through `&mut self`
+    /// low, but lookups through `&self` don't splay, and each takes time
+    /// proportional to the depth of the tree. After inserting keys in order,
+    /// for example, that is O(n). This bounds it at O(log n) until the tree is
+    /// next modified, and takes O(n) time.
+    pub fn rebalance(&mut self) {
+        let size = self.size;
+        let mut entries = mem::take(self).into_iter();
+        *self.root_mut() = build_balanced(&mut entries, size);
+        self.size = size;
+    }
+
+    /// Returns the maximum and average depth of the nodes in the tree, where
+    /// the root has depth 1, or `(0, 0.0)` if the map is empty.
+    ///
+    /// Lookups through `&self` take time proportional to the depth of the
+    /// node they find, so a maximum depth far above log2 of `len` suggests
+    /// calling `rebalance`. This walks the whole tree with an explicit stack,
+    /// so it takes O(n) time but doesn't recurse, however deep the tree is.
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let mut map: SplayMap<_, _> = (0..7).map(|key| (key, ())).collect();
+    /// assert_eq!(map.depth_stats().0, 7);
+    /// map.rebalance();
+    /// assert_eq!(map.depth_stats(), (3, 17.0 / 7.0));
+    /// ```
+    pub fn depth_stats(&self) -> (usize, f64) {
+        let (mut max_depth, mut total_depth) = (0, 0);
+        let mut stack = vec![(self.root_ref(), 1)];
+        while let Some((node, depth)) = stack.pop() {
+            if let Some(ref node) = *node {
+                max_depth = cmp::max(max_depth, depth);
+                total_depth += depth;
+                stack.push((&node.left, depth + 1));
+                stack.push((&node.right, depth + 1));
+            }
+        }
+        if self.size == 0 {
+            return (0, 0.0)
+        }
+        (max_depth, total_depth as f64 / self.size as f64)
+    }
+
     /// Return a reference to the value corresponding to the key
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
-        where K: Borrow<Q>, Q: Ord,
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let map: SplayMap<_, _> = vec![(10, "ten"), (20, "twenty")].into_iter().collect();
+    /// assert_eq!(map.get(&20), Some(&"twenty"));
+    /// assert_eq!(map.get(&15), None);
+    /// ```
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: ?Sized + Ord,
     {
-        // Splay trees are self-modifying, so they can't exactly operate with
-        // the immutable self given by the Map interface for this method. It can
-        // be guaranteed, however, that the callers of this method are not
-        // modifying the tree, they may just be rotating it. Each node is a
-        // pointer on the heap, so we know that none of the pointers inside
-        // these nodes (the value returned from this function) will be moving.
-        //
-        // With this in mind, we can unsafely use a mutable version of this tree
-        // to invoke the splay operation and return a pointer to the inside of
-        // one of the nodes (the pointer won't be deallocated or moved).
-        //
-        // However I'm not entirely sure whether this works with iteration or
-        // not. Arbitrary lookups can occur during iteration, and during
-        // iteration there's some form of "stack" remembering the nodes that
-        // need to get visited. I don't believe that it's safe to allow lookups
-        // while the tree is being iterated. Right now there are no iterators
-        // exposed on this splay tree implementation, and more thought would be
-        // required if there were.
-        unsafe {
-            match *self.root.get() {
-                Some(ref mut root) => {
-                    splay_with_key(key, root);
-                    if key == root.key_value.0.borrow() {
-                        return Some(&root.key_value.1);
-                    }
-                    None
-                }
-                None => None,
+        // Splay trees are self-modifying, but this lookup deliberately does
+        // not splay. Borrowing iterators hold a stack of pointers into the
+        // tree, and rotating nodes out from underneath them while they are
+        // alive would invalidate that stack. Since `get` only needs `&self`,
+        // it could otherwise be called in the middle of an iteration, so we
+        // just walk down the tree instead.
+        let mut node = self.root_ref();
+        while let Some(ref current) = *node {
+            match key.cmp(current.key_value.0.borrow()) {
+                Less => node = &current.left,
+                Greater => node = &current.right,
+                Equal => return Some(&current.key_value.1),
             }
         }
+        None
     }
 
     /// Return a mutable reference to the value corresponding to the key
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
-        where K: Borrow<Q>, Q: Ord,
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>, Q: ?Sized + Ord,
     {
         match *self.root_mut() {
             None => { return None; }
@@ -210,6 +525,14 @@ impl<K: Ord, V> SplayMap<K, V> {
         }
     }
 
+    /// Searches for an entry using a comparison function instead of a key,
+    /// splaying it to the root and returning a mutable reference to it.
+    ///
+    /// This is an advanced API. The function is given each key and value
+    /// visited and must return `Less` if the entry sought comes before it,
+    /// `Greater` if it comes after it, and `Equal` if it is the one. The
+    /// function must be consistent with the key order, and the key must not be
+    /// modified in a way that changes its position in that order.
     pub fn get_with_mut<Q>(&mut self, mut compare: Q) -> Option<&mut (K, V)>
                            where Q: FnMut(&K, &V) -> Ordering {
         match *self.root_mut() {
@@ -225,50 +548,121 @@ impl<K: Ord, V> SplayMap<K, V> {
         }
     }
 
+    /// Searches for an entry using a comparison function as `get_with_mut`
+    /// does, but without splaying, so that only `&self` is needed.
+    ///
+    /// Because the tree is left as it is, repeated lookups of the same entry
+    /// don't get cheaper and a search on a skewed tree can cost O(n); the
+    /// amortized bounds of the splay tree only hold for splaying operations.
+    /// Prefer `get_with_mut` when exclusive access is available.
+    pub fn get_with<Q>(&self, mut compare: Q) -> Option<(&K, &V)>
+                       where Q: FnMut(&K, &V) -> Ordering {
+        let mut node = self.root_ref();
+        while let Some(ref current) = *node {
+            match compare(&current.key_value.0, &current.key_value.1) {
+                Less => node = &current.left,
+                Greater => node = &current.right,
+                Equal => return Some((&current.key_value.0, &current.key_value.1)),
+            }
+        }
+        None
+    }
+
+    /// Returns the first entry for which the comparison function does not
+    /// return `Greater`, assuming that it returns `Greater` for a prefix of
+    /// the entries and `Less` or `Equal` for the rest. The tree is not
+    /// splayed.
+    ///
+    /// This is an advanced API; see `get_with_mut` for the requirements on the
+    /// comparison function.
     pub fn lower_bound_with<Q>(&self, compare: Q) -> Option<&(K, V)>
                                where Q: FnMut(&K, &V) -> Ordering {
         self.root_ref().as_ref().and_then(|root| lower_bound_with(compare, root))
     }
 
-    /// Insert a key-value pair from the map. If the key already had a value
+    /// The mirror image of `lower_bound_with`: returns the last entry for
+    /// which the comparison is not `Less`, assuming that the comparison is
+    /// `Greater` or `Equal` for a prefix of the entries and `Less` for the
+    /// rest. The tree is not splayed.
+    ///
+    /// Like `lower_bound_with`, this takes `&self` and returns the entry by
+    /// reference, rather than splaying through `&mut self` and copying out the
+    /// key. That keeps it usable from `&self` queries, such as the ones that
+    /// `Exclusions` builds on `lower_bound_with`, and works for keys that
+    /// aren't `Copy`.
+    ///
+    /// This is an advanced API; see `get_with_mut` for the requirements on the
+    /// comparison function.
+    pub fn upper_bound_with<Q>(&self, compare: Q) -> Option<&(K, V)>
+                               where Q: FnMut(&K, &V) -> Ordering {
+        self.root_ref().as_ref().and_then(|root| upper_bound_with(compare, root))
+    }
+
+    /// Insert a key-value pair into the map. If the key already had a value
     /// present in the map, that value is returned. Otherwise None is returned.
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let mut map = SplayMap::new();
+    /// assert_eq!(map.insert(2, "b"), None);
+    /// assert_eq!(map.insert(1, "a"), None);
+    /// assert_eq!(map.insert(2, "B"), Some("b"));
+    /// assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(1, "a"), (2, "B")]);
+    /// ```
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        match self.root_mut() {
-            &mut Some(ref mut root) => {
-                splay_with_key(&key, root);
+        if let Some(ref mut root) = *self.root_mut() {
+            splay_with_key(&key, root);
+            if key == root.key_value.0 {
+                let old = mem::replace(&mut root.key_value.1, value);
+                return Some(old);
+            }
+        }
+        self.insert_at_root(key, value);
+        return None;
+    }
 
-                match key.cmp(&root.key_value.0) {
-                    Equal => {
-                        let old = mem::replace(&mut root.key_value.1, value);
-                        return Some(old);
-                    }
-                    /* TODO: would unsafety help perf here? */
-                    Less => {
-                        let left = root.pop_left();
-                        let new = Node::new(key, value, left, None);
-                        let prev = mem::replace(root, new);
-                        root.right = Some(prev);
-                    }
-                    Greater => {
-                        let right = root.pop_right();
-                        let new = Node::new(key, value, None, right);
-                        let prev = mem::replace(root, new);
-                        root.left = Some(prev);
-                    }
-                }
+    /// Gets the entry for the given key, for in-place manipulation. The tree
+    /// is splayed only once, no matter what is then done with the entry.
+    pub fn entry<'a>(&'a mut self, key: K) -> Entry<'a, K, V> {
+        let occupied = match *self.root_mut() {
+            None => false,
+            Some(ref mut root) => {
+                splay_with_key(&key, root);
+                key == root.key_value.0
             }
-            slot => {
-                *slot = Some(Node::new(key, value, None, None));
+        };
+        if occupied {
+            let root = self.root_mut().as_mut().unwrap();
+            Entry::Occupied(OccupiedEntry { key_value: &mut root.key_value })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, key })
+        }
+    }
+
+    /// Returns the value for the given key, first inserting the value returned
+    /// by the given function if the key is not present. The function is only
+    /// called if the key is not present, and the tree is splayed only once.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V
+                                 where F: FnOnce() -> V {
+        let occupied = match *self.root_mut() {
+            None => false,
+            Some(ref mut root) => {
+                splay_with_key(&key, root);
+                key == root.key_value.0
             }
+        };
+        if occupied {
+            &mut self.root_mut().as_mut().unwrap().key_value.1
+        } else {
+            self.insert_at_root(key, f())
         }
-        self.size += 1;
-        return None;
     }
 
     /// Removes a key from the map, returning the value at the key if the key
     /// was previously in the map.
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
-        where K: Borrow<Q>, Q: Ord
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>, Q: ?Sized + Ord
     {
         match *self.root_mut() {
             None => { return None; }
@@ -295,9 +689,203 @@ impl<K: Ord, V> SplayMap<K, V> {
         self.size -= 1;
         return Some(value);
     }
+
+    /// Returns the entry with the greatest key less than or equal to the given
+    /// key, if any, splaying the tree around that key.
+    pub fn floor<Q>(&mut self, key: &Q) -> Option<(&K, &V)>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        let root = self.root_mut().as_mut()?;
+        splay_with_key(key, root);
+        // The root is now the closest key on one side or the other. If it is
+        // too great, the floor is the greatest key to its left.
+        let mut node: &Node<K, V> = root;
+        if node.key_value.0.borrow() > key {
+            node = node.left.as_ref()?;
+            while let Some(ref right) = node.right {
+                node = right;
+            }
+        }
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    /// Returns the entry with the least key greater than or equal to the given
+    /// key, if any, splaying the tree around that key.
+    pub fn ceiling<Q>(&mut self, key: &Q) -> Option<(&K, &V)>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        let root = self.root_mut().as_mut()?;
+        splay_with_key(key, root);
+        let mut node: &Node<K, V> = root;
+        if node.key_value.0.borrow() < key {
+            node = node.right.as_ref()?;
+            while let Some(ref left) = node.left {
+                node = left;
+            }
+        }
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    /// Returns the entry with the least key, if any. The tree is not splayed.
+    pub fn peek_min(&self) -> Option<(&K, &V)> {
+        let mut node: &Node<K, V> = self.root_ref().as_ref()?;
+        while let Some(ref left) = node.left {
+            node = left;
+        }
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    /// Returns the entry with the greatest key, if any. The tree is not
+    /// splayed.
+    pub fn peek_max(&self) -> Option<(&K, &V)> {
+        let mut node: &Node<K, V> = self.root_ref().as_ref()?;
+        while let Some(ref right) = node.right {
+            node = right;
+        }
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    /// Splits the map in two at the given key, returning a new map with all
+    /// the entries whose keys are greater than or equal to it and leaving the
+    /// rest in this map.
+    ///
+    /// The tree is splayed once and then cut, but the entries split off are
+    /// counted to keep both lengths correct, which takes time linear in their
+    /// number.
+    ///
+    /// ```
+    /// use buoyancy::map::SplayMap;
+    ///
+    /// let mut map: SplayMap<_, _> = (0..6).map(|i| (i, i * 10)).collect();
+    /// let upper = map.split_off(&4);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    /// assert_eq!(upper.keys().cloned().collect::<Vec<_>>(), vec![4, 5]);
+    /// ```
+    pub fn split_off<Q>(&mut self, key: &Q) -> SplayMap<K, V>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        let upper = match *self.root_mut() {
+            None => None,
+            Some(ref mut root) => {
+                splay_with_key(key, root);
+                if root.key_value.0.borrow() >= key {
+                    None
+                } else {
+                    Some(root.pop_right())
+                }
+            }
+        };
+        let upper = match upper {
+            Some(upper) => upper,
+            None => {
+                // The root and everything to its right belong in the new map.
+                let lower = self.root_mut().as_mut().and_then(|root| root.pop_left());
+                mem::replace(self.root_mut(), lower)
+            }
+        };
+        let upper_size = count_nodes(&upper);
+        self.size -= upper_size;
+        SplayMap { root: UnsafeCell::new(upper), size: upper_size }
+    }
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref entry) => &entry.key_value.0,
+            Entry::Vacant(ref entry) => &entry.key,
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Entry<'a, K, V> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    /// Returns a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        &self.key_value.1
+    }
+
+    /// Returns a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.key_value.1
+    }
+
+    /// Converts the entry into a mutable reference to its value.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.key_value.1
+    }
+
+    /// Sets the value of the entry, returning the old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(&mut self.key_value.1, value)
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    /// Sets the value of the entry, returning a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.insert_at_root(self.key, value)
+    }
 }
 
 impl<K: Ord, V> SplayMap<K, V> {
+    /// Inserts a key that is not present in the map as the new root. The tree
+    /// must have just been splayed with the key, so that the root is the
+    /// closest key to it.
+    fn insert_at_root(&mut self, key: K, value: V) -> &mut V {
+        match self.root_mut() {
+            &mut Some(ref mut root) => {
+                /* TODO: would unsafety help perf here? */
+                if key < root.key_value.0 {
+                    let left = root.pop_left();
+                    let new = Node::new(key, value, left, None);
+                    let prev = mem::replace(root, new);
+                    root.right = Some(prev);
+                } else {
+                    let right = root.pop_right();
+                    let new = Node::new(key, value, None, right);
+                    let prev = mem::replace(root, new);
+                    root.left = Some(prev);
+                }
+            }
+            slot => {
+                *slot = Some(Node::new(key, value, None, None));
+            }
+        }
+        self.size += 1;
+        &mut self.root_mut().as_mut().unwrap().key_value.1
+    }
+
     // These two functions provide safe access to the root node, and they should
     // be valid to call in virtually all contexts.
     fn root_mut(&mut self) -> &mut Option<Box<Node<K, V>>> {
@@ -306,6 +894,33 @@ impl<K: Ord, V> SplayMap<K, V> {
     fn root_ref(&self) -> &Option<Box<Node<K, V>>> {
         unsafe { &*self.root.get() }
     }
+
+    /// Hashes the shape of the tree along with its keys, for tests that check
+    /// that an operation leaves the tree untouched.
+    #[cfg(all(test, feature = "std"))]
+    pub fn structure_hash(&self) -> u64 where K: Hash {
+        let mut hasher = DefaultHasher::new();
+        let mut stack = vec![self.root_ref()];
+        while let Some(node) = stack.pop() {
+            match *node {
+                None => hasher.write_u8(0),
+                Some(ref node) => {
+                    hasher.write_u8(1);
+                    node.key_value.0.hash(&mut hasher);
+                    stack.push(&node.right);
+                    stack.push(&node.left);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a
+    /// leaf.
+    #[cfg(test)]
+    pub fn depth(&self) -> usize {
+        self.depth_stats().0
+    }
 }
 
 impl<'a, K: Ord, V, Q: ?Sized> Index<&'a Q> for SplayMap<K, V>
@@ -345,6 +960,24 @@ impl<K: Ord, V> Extend<(K, V)> for SplayMap<K, V> {
     }
 }
 
+impl<K: Ord, V> IntoIterator for SplayMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    /// Moves all values out of this map, transferring ownership to the given
+    /// iterator.
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        IntoIter { cur: self.root_mut().take(), remaining: self.size }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SplayMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
 impl<K, V> Iterator for IntoIter<K, V> {
     type Item = (K, V);
     fn next(&mut self) -> Option<(K, V)> {
@@ -410,6 +1043,158 @@ impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
 
 impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
 
+impl<K, V> Drop for IntoIter<K, V> {
+    fn drop(&mut self) {
+        // Dropping the remaining tree directly would recurse once per level, which can overflow
+        // the stack for a degenerate tree, so rotate the nodes off one by one instead.
+        for _ in self.by_ref() {
+            // ignore, drop the values (and the node)
+        }
+    }
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left_spine(&mut self, mut node: &'a Option<Box<Node<K, V>>>) {
+        while let Some(ref current) = *node {
+            self.stack.push(current);
+            node = &current.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        self.remaining -= 1;
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> IterRev<'a, K, V> {
+    fn push_right_spine(&mut self, mut node: &'a Option<Box<Node<K, V>>>) {
+        while let Some(ref current) = *node {
+            self.stack.push(current);
+            node = &current.right;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterRev<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_right_spine(&node.left);
+        self.remaining -= 1;
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterRev<'a, K, V> {}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    fn push_left_spine(&mut self, mut node: &'a mut Option<Box<Node<K, V>>>) {
+        loop {
+            match *{node} {
+                Some(ref mut current) => {
+                    let Node { ref mut key_value, ref mut left, ref mut right } = **current;
+                    self.stack.push((&key_value.0, &mut key_value.1, right));
+                    node = left;
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let (key, value, right) = self.stack.pop()?;
+        self.push_left_spine(right);
+        self.remaining -= 1;
+        Some((key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        let within_upper = match self.upper {
+            Bound::Included(ref upper) => node.key_value.0 <= *upper,
+            Bound::Excluded(ref upper) => node.key_value.0 < *upper,
+            Bound::Unbounded => true,
+        };
+        if !within_upper {
+            self.stack.clear();
+            return None
+        }
+        let mut child = &node.right;
+        while let Some(ref current) = *child {
+            self.stack.push(current);
+            child = &current.left;
+        }
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+}
+
 impl<K: Clone + Ord, V: Clone> Clone for SplayMap<K, V> {
     fn clone(&self) -> SplayMap<K, V> {
         SplayMap {
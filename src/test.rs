@@ -2,10 +2,20 @@
 // http://creativecommons.org/publicdomain/zero/1.0/
 
 use app_units::Au;
-use exclusions::{Exclusions, Point, Side, Size};
-use quickcheck::{Arbitrary, Gen};
-use std::cmp;
+use columns::Columns;
+use exclusions::{BuildError, Clear, Direction, Exclusions, ExclusionsBuilder, InvalidBands,
+                 LogicalMargins, MismatchedZones, NegativeInlineSize, Placement, Point, Side, Size,
+                 WritingMode};
+use map::SplayMap;
+use quickcheck::{Arbitrary, Gen, QuickCheck, empty_shrinker, single_shrinker};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::cmp::{self, Ordering};
+use std::collections::BTreeMap;
 use std::i32;
+use std::mem;
+use std::ops::Bound;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct InlineSize(Au);
@@ -14,6 +24,10 @@ impl Arbitrary for InlineSize {
     fn arbitrary<G: Gen>(gen: &mut G) -> InlineSize {
         InlineSize(Au(i32::abs(Arbitrary::arbitrary(gen))))
     }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = InlineSize>> {
+        Box::new((self.0).0.shrink().map(|inline_size| InlineSize(Au(inline_size))))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -32,6 +46,15 @@ impl Arbitrary for Exclusion {
             },
         }
     }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Exclusion>> {
+        Box::new((self.side, self.size).shrink().map(|(side, size)| {
+            Exclusion {
+                side,
+                size,
+            }
+        }))
+    }
 }
 
 impl Arbitrary for Size {
@@ -41,6 +64,12 @@ impl Arbitrary for Size {
             block: Au(Arbitrary::arbitrary(gen)),
         }
     }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Size>> {
+        Box::new((self.inline.0, self.block.0).shrink().map(|(inline, block)| {
+            Size::new(Au(inline), Au(block))
+        }))
+    }
 }
 
 impl Arbitrary for Side {
@@ -51,6 +80,13 @@ impl Arbitrary for Side {
             Side::Right
         }
     }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Side>> {
+        match *self {
+            Side::Left => empty_shrinker(),
+            Side::Right => single_shrinker(Side::Left),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -96,17 +132,41 @@ pub fn place(inline_size: InlineSize, mut exclusion_info: Vec<Exclusion>) -> Vec
     for exclusion in &mut exclusion_info {
         exclusion.size.inline = cmp::min(exclusion.size.inline, inline_size.0);
         let origin = exclusions.place(exclusion.side, &exclusion.size).origin;
-        let exclusion_inline_size = match exclusion.side {
-            Side::Left => origin.inline + exclusion.size.inline,
-            Side::Right => inline_size.0 - origin.inline,
-        };
-        exclusions.exclude(exclusion.side,
-                           &Size::new(exclusion_inline_size, origin.block + exclusion.size.block));
-        areas.push(ExcludedArea::new(exclusion, &origin))
+        let area = ExcludedArea::new(exclusion, &origin);
+        exclude(&mut exclusions, inline_size, &area);
+        areas.push(area)
     }
     areas
 }
 
+pub fn exclusions(inline_size: InlineSize, areas: &[ExcludedArea]) -> Exclusions {
+    let mut exclusions = Exclusions::new(inline_size.0);
+    for area in areas {
+        exclude(&mut exclusions, inline_size, area)
+    }
+    exclusions
+}
+
+pub fn exclude(exclusions: &mut Exclusions, inline_size: InlineSize, area: &ExcludedArea) {
+    let exclusion_inline_size = match area.exclusion.side {
+        Side::Left => area.origin.inline + area.exclusion.size.inline,
+        Side::Right => inline_size.0 - area.origin.inline,
+    };
+    exclusions.exclude(area.exclusion.side,
+                       &Size::new(exclusion_inline_size,
+                                  area.origin.block + area.exclusion.size.block));
+    exclusions.assert_invariants();
+}
+
+/// Reorders the given items by swapping each one with the item at the matching index in `swaps`,
+/// so that quickcheck can generate and shrink the order along with the rest of the input.
+pub fn permute<T>(items: &mut [T], swaps: &[usize]) {
+    let len = items.len();
+    for (index, &swap) in swaps.iter().enumerate().take(len) {
+        items.swap(index, swap % len)
+    }
+}
+
 quickcheck! {
     fn check_overflow(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
         let areas = place(inline_size, exclusions);
@@ -177,5 +237,2546 @@ quickcheck! {
         }
         true
     }
+
+    fn check_remove(inline_size: InlineSize,
+                    exclusions: Vec<Exclusion>,
+                    keep: Vec<bool>,
+                    swaps: Vec<usize>)
+                    -> bool {
+        let areas = place(inline_size, exclusions);
+        let (mut all, mut survivors) = (Exclusions::new(inline_size.0),
+                                        Exclusions::new(inline_size.0));
+        let mut removed = vec![];
+        for (i, area) in areas.iter().enumerate() {
+            exclude(&mut all, inline_size, area);
+            if keep.get(i).cloned().unwrap_or(true) {
+                exclude(&mut survivors, inline_size, area)
+            } else {
+                removed.push(area)
+            }
+        }
+        permute(&mut removed, &swaps);
+        for area in removed {
+            all.remove(area.exclusion.side, &area.origin, &area.exclusion.size);
+            all.assert_invariants();
+        }
+        for area in &areas {
+            for &side in &[Side::Left, Side::Right] {
+                assert_eq!(all.place(side, &area.exclusion.size),
+                           survivors.place(side, &area.exclusion.size))
+            }
+        }
+        true
+    }
+
+    fn check_bands(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        exclusions.assert_invariants();
+        true
+    }
+
+    fn check_near_max_block_sizes(inline_size: InlineSize,
+                                  exclusions: Vec<(Side, u16, u16)>,
+                                  full_width: (u16, u16),
+                                  size: Size)
+                                  -> bool {
+        let mut zone = Exclusions::new(inline_size.0);
+        for &(side, inline, offset) in &exclusions {
+            zone.exclude(side, &Size::new(Au(inline as i32), Au(i32::MAX - offset as i32)));
+            zone.assert_invariants();
+        }
+        zone.exclude_full_width(Au(i32::MAX - full_width.0 as i32), Au(full_width.1 as i32));
+        zone.assert_invariants();
+        for &side in &[Side::Left, Side::Right] {
+            zone.place(side, &size);
+        }
+        true
+    }
+
+    fn check_clear(inline_size: InlineSize, first: Exclusion, second: Exclusion) -> bool {
+        let areas = place(inline_size, vec![first]);
+        let mut exclusions = self::exclusions(inline_size, &areas);
+        let clear = match areas[0].exclusion.side {
+            Side::Left => Clear::Left,
+            Side::Right => Clear::Right,
+        };
+        let placement = exclusions.place_with_clear(second.side, clear, &second.size);
+        if areas[0].exclusion.size.inline > Au(0) {
+            assert!(placement.origin.block >= areas[0].origin.block +
+                    areas[0].exclusion.size.block)
+        }
+        true
+    }
+
+    fn check_try_place(inline_size: InlineSize, exclusions: Vec<Exclusion>, size: Size)
+                       -> bool {
+        let mut exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        for &side in &[Side::Left, Side::Right] {
+            match exclusions.try_place(side, &size) {
+                None => assert!(size.inline > inline_size.0),
+                Some(placement) => {
+                    assert!(size.inline <= inline_size.0);
+                    assert_eq!(placement, exclusions.place(side, &size))
+                }
+            }
+        }
+        true
+    }
+
+    fn check_set_inline_size(inline_size: InlineSize,
+                             exclusions: Vec<Exclusion>,
+                             new_inline_size: InlineSize)
+                             -> bool {
+        let mut exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let bands: Vec<_> = exclusions.bands().collect();
+        exclusions.set_inline_size(new_inline_size.0);
+        exclusions.assert_invariants();
+        assert_eq!(exclusions.inline_size(), new_inline_size.0);
+        exclusions.set_inline_size(inline_size.0);
+        assert_eq!(exclusions.bands().collect::<Vec<_>>(), bands);
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn check_serde_round_trip(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let exclusions = self::exclusions(inline_size, &areas);
+        let mut serializer = tokens::Serializer(vec![]);
+        exclusions.serialize(&mut serializer).unwrap();
+        let mut deserializer = tokens::Deserializer(serializer.0.into_iter());
+        let mut round_tripped: Exclusions = Deserialize::deserialize(&mut deserializer).unwrap();
+        round_tripped.assert_invariants();
+        assert_eq!(round_tripped.bands().collect::<Vec<_>>(),
+                   exclusions.bands().collect::<Vec<_>>());
+        for area in &areas {
+            assert!(round_tripped.remove(area.exclusion.side, &area.origin, &area.exclusion.size) ||
+                    area.exclusion.size.inline == Au(0) || area.exclusion.size.block == Au(0));
+        }
+        true
+    }
+
+    fn check_rtl_mirrors_ltr(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let mut ltr = Exclusions::with_direction(inline_size.0, Direction::Ltr);
+        let mut rtl = Exclusions::with_direction(inline_size.0, Direction::Rtl);
+        for mut exclusion in exclusions {
+            exclusion.size.inline = cmp::min(exclusion.size.inline, inline_size.0);
+            let ltr_origin = ltr.place(exclusion.side, &exclusion.size).origin;
+            let rtl_origin = rtl.place(exclusion.side, &exclusion.size).origin;
+            assert_eq!(rtl_origin.inline,
+                       inline_size.0 - ltr_origin.inline - exclusion.size.inline);
+            assert_eq!(rtl_origin.block, ltr_origin.block);
+
+            let inline_size = match exclusion.side {
+                Side::Left => ltr_origin.inline + exclusion.size.inline,
+                Side::Right => inline_size.0 - ltr_origin.inline,
+            };
+            let area = Size::new(inline_size, ltr_origin.block + exclusion.size.block);
+            ltr.exclude(exclusion.side, &area);
+            rtl.exclude(exclusion.side, &area);
+            ltr.assert_invariants();
+            rtl.assert_invariants();
+        }
+        for ((ltr_start, ltr_length, ltr_left, ltr_right),
+             (rtl_start, rtl_length, rtl_left, rtl_right)) in ltr.bands().zip(rtl.bands()) {
+            assert_eq!((ltr_start, ltr_length, ltr_left, ltr_right),
+                       (rtl_start, rtl_length, rtl_right, rtl_left));
+        }
+        true
+    }
+
+    fn check_map_range(keys: Vec<i32>, lower: i32, upper: i32) -> bool {
+        let (lower, upper) = (cmp::min(lower, upper), cmp::max(lower, upper) + 1);
+        let map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let reference: BTreeMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let lower_bounds = [Bound::Included(lower), Bound::Excluded(lower), Bound::Unbounded];
+        let upper_bounds = [Bound::Included(upper), Bound::Excluded(upper), Bound::Unbounded];
+        for lower in &lower_bounds {
+            for upper in &upper_bounds {
+                assert_eq!(map.range(*lower, *upper).collect::<Vec<_>>(),
+                           reference.range((*lower, *upper)).collect::<Vec<_>>());
+            }
+        }
+        true
+    }
+
+    fn check_map_keys_and_values(keys: Vec<i32>) -> bool {
+        let mut map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let mut sorted_keys = keys.clone();
+        sorted_keys.sort();
+        sorted_keys.dedup();
+        assert_eq!(map.keys().cloned().collect::<Vec<_>>(), sorted_keys);
+        assert_eq!(map.values().cloned().collect::<Vec<_>>(),
+                   sorted_keys.iter().map(|&key| -key).collect::<Vec<_>>());
+        for value in map.values_mut() {
+            *value = -*value
+        }
+        assert!(map.iter().all(|(key, value)| key == value));
+        assert_eq!(map.iter_mut().len(), sorted_keys.len());
+        true
+    }
+
+    fn check_map_extend(initial: Vec<(i32, i32)>, extension: Vec<(i32, i32)>) -> bool {
+        let mut extended: SplayMap<i32, i32> = initial.iter().cloned().collect();
+        let mut inserted: SplayMap<i32, i32> = initial.iter().cloned().collect();
+        extended.extend(extension.iter().cloned());
+        for &(key, value) in &extension {
+            inserted.insert(key, value);
+        }
+        assert_eq!(extended.len(), inserted.len());
+        extended.iter().eq(inserted.iter())
+    }
+
+    fn check_map_split_off(keys: Vec<i32>, split_key: i32) -> bool {
+        let mut map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let all: Vec<_> = map.iter().map(|(&key, &value)| (key, value)).collect();
+        let upper = map.split_off(&split_key);
+        assert!(map.keys().all(|&key| key < split_key));
+        assert!(upper.keys().all(|&key| key >= split_key));
+        assert_eq!(map.len(), map.iter().count());
+        assert_eq!(upper.len(), upper.iter().count());
+        let rejoined: Vec<_> = map.iter().chain(upper.iter()).map(|(&key, &value)| {
+            (key, value)
+        }).collect();
+        rejoined == all
+    }
+
+    fn check_map_floor_and_ceiling(keys: Vec<i32>, queries: Vec<i32>) -> bool {
+        let mut map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let reference: BTreeMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        for &query in &queries {
+            assert_eq!(map.floor(&query),
+                       reference.range((Bound::Unbounded, Bound::Included(query))).next_back());
+            assert_eq!(map.ceiling(&query),
+                       reference.range((Bound::Included(query), Bound::Unbounded)).next());
+        }
+        true
+    }
+
+    fn check_map_peek_min_and_max(keys: Vec<i32>, queries: Vec<i32>) -> bool {
+        let mut map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        for &query in &queries {
+            map.floor(&query);
+        }
+        let structure = map.structure_hash();
+        let reference: BTreeMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        assert_eq!(map.peek_min(), reference.iter().next());
+        assert_eq!(map.peek_max(), reference.iter().next_back());
+        map.structure_hash() == structure
+    }
+
+    fn check_map_retain(keys: Vec<i32>, divisor: u8, queries: Vec<i32>) -> bool {
+        let divisor = divisor as i32 + 1;
+        let mut map: SplayMap<i32, i32> = keys.iter().map(|&key| (key, -key)).collect();
+        let mut reference: BTreeMap<i32, i32> = BTreeMap::new();
+        for &key in &keys {
+            if key % divisor == 0 {
+                reference.insert(key, 1 - key);
+            }
+        }
+        map.retain(|&key, value| {
+            *value += 1;
+            key % divisor == 0
+        });
+        for &query in &queries {
+            assert_eq!(map.floor(&query),
+                       reference.range((Bound::Unbounded, Bound::Included(query))).next_back());
+        }
+        assert_eq!(map.len(), reference.len());
+        map.iter().eq(reference.iter())
+    }
+
+    fn check_reverse_iteration(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let mut bands: Vec<_> = exclusions.bands().collect();
+        bands.reverse();
+        assert_eq!(exclusions.bands_rev().collect::<Vec<_>>(), bands);
+
+        let map: SplayMap<Au, usize> =
+            bands.iter().enumerate().map(|(i, band)| (band.0, i)).collect();
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.reverse();
+        assert_eq!(map.iter_rev().collect::<Vec<_>>(), entries);
+        true
+    }
+
+    fn check_exclude_full_width(inline_size: InlineSize,
+                                exclusions: Vec<Exclusion>,
+                                block_start: i32,
+                                block_size: i32,
+                                size: Size)
+                                -> bool {
+        let (block_start, block_size) = (Au(i32::abs(block_start)), Au(i32::abs(block_size)));
+        let mut exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let before = exclusions.clone();
+        exclusions.exclude_full_width(block_start, block_size);
+        exclusions.assert_invariants();
+        for (band_block_start, length, left, right) in exclusions.bands() {
+            if length == Au(0) {
+                return false
+            }
+            if band_block_start < block_start + block_size && block_size > Au(0) &&
+                    band_block_start + length > block_start {
+                assert!(left + right >= inline_size.0)
+            } else {
+                assert_eq!(exclusions.insets_at(band_block_start),
+                           before.insets_at(band_block_start))
+            }
+        }
+        let size = Size::new(cmp::min(Au(i32::abs(size.inline.0)), inline_size.0),
+                             Au(i32::abs(size.block.0)));
+        for &side in &[Side::Left, Side::Right] {
+            assert!(exclusions.place(side, &size).origin.block >= block_start + block_size ||
+                    block_size == Au(0))
+        }
+        true
+    }
+
+    fn check_negative_margin_boxes(inline_size: InlineSize, exclusions: Vec<(Side, Size)>)
+                                   -> bool {
+        let mut zone = Exclusions::new(inline_size.0);
+        for &(side, size) in &exclusions {
+            let placement = zone.place(side, &size);
+            assert!(placement.origin.block >= Au(0));
+            assert!(placement.available_inline_size >= Au(0));
+            let (left, right) = zone.insets_at(placement.origin.block);
+            match side {
+                Side::Left => assert_eq!(placement.origin.inline, left),
+                Side::Right => {
+                    assert_eq!(placement.origin.inline + size.inline, inline_size.0 - right)
+                }
+            }
+            let area = ExcludedArea::new(&Exclusion { side, size }, &placement.origin);
+            exclude(&mut zone, inline_size, &area);
+        }
+        true
+    }
+
+    fn check_exclude_at(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = Exclusions::new(inline_size.0);
+        for area in &areas {
+            zone.exclude_at(area.exclusion.side, &area.origin, &area.exclusion.size);
+            zone.assert_invariants();
+        }
+        zone.bands().eq(self::exclusions(inline_size, &areas).bands())
+    }
+
+    fn check_exclude_reporting(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = Exclusions::new(inline_size.0);
+        for area in &areas {
+            let available_width =
+                zone.exclude_reporting(area.exclusion.side, &area.origin, &area.exclusion.size);
+            assert_eq!(available_width, zone.available_width(area.origin.block, Au(0)));
+        }
+        zone.bands().eq(self::exclusions(inline_size, &areas).bands())
+    }
+
+    fn check_exclude_span_from_top(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let (mut spans, mut areas) = (Exclusions::new(inline_size.0),
+                                      Exclusions::new(inline_size.0));
+        for exclusion in &exclusions {
+            spans.exclude_span(exclusion.side, exclusion.size.inline, Au(0), exclusion.size.block);
+            spans.assert_invariants();
+            areas.exclude(exclusion.side, &exclusion.size);
+        }
+        spans.bands().eq(areas.bands())
+    }
+
+    fn check_place_with_inset(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let mut zone = Exclusions::new(inline_size.0);
+        for mut exclusion in exclusions {
+            exclusion.size.inline = cmp::min(exclusion.size.inline, inline_size.0);
+            let (side, size) = (exclusion.side, exclusion.size);
+            let (placement, inset) = zone.place_with_inset(side, &size);
+            assert_eq!(placement, zone.place(side, &size));
+            let block_start = placement.origin.block;
+            zone.exclude_span(side, inset, block_start, block_start + size.block);
+            if size.block > Au(0) {
+                let insets = zone.insets_at(placement.origin.block);
+                assert_eq!(inset, if side == Side::Left { insets.0 } else { insets.1 });
+            }
+        }
+        true
+    }
+
+    fn check_translate_block(inline_size: InlineSize,
+                             exclusions: Vec<Exclusion>,
+                             full_width: Option<(u8, u8)>,
+                             delta: i16)
+                             -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        if let Some((block_start, block_size)) = full_width {
+            zone.exclude_full_width(Au(block_start as i32), Au(block_size as i32));
+        }
+        let before = zone.clone();
+        let delta = Au(delta as i32);
+        zone.translate_block(delta);
+        zone.assert_invariants();
+        let mut block_positions: Vec<Au> = before.bands().map(|band| band.0).collect();
+        block_positions.extend(before.bands().map(|band| band.0 - Au(1)));
+        for &block_position in &block_positions {
+            if block_position >= Au(0) && block_position + delta >= Au(0) {
+                assert_eq!(zone.insets_at(block_position + delta), before.insets_at(block_position))
+            }
+        }
+        if delta > Au(0) {
+            assert_eq!(zone.insets_at(delta - Au(1)), (Au(0), Au(0)));
+        }
+        let mut rebuilt = zone.clone();
+        rebuilt.set_inline_size(inline_size.0);
+        rebuilt.bands().eq(zone.bands())
+    }
+
+    fn check_append(inline_size: InlineSize, first: Vec<Exclusion>, second: Vec<Exclusion>)
+                    -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, first));
+        let second = place(inline_size, second);
+        let other = self::exclusions(inline_size, &second);
+        let at_block = zone.height();
+        let mut combined = zone.clone();
+        for area in &second {
+            let inset = match area.exclusion.side {
+                Side::Left => area.origin.inline + area.exclusion.size.inline,
+                Side::Right => inline_size.0 - area.origin.inline,
+            };
+            combined.exclude_span(area.exclusion.side,
+                                  inset,
+                                  at_block,
+                                  at_block + area.origin.block + area.exclusion.size.block);
+        }
+        zone.append(other.clone(), at_block).unwrap();
+        zone.assert_invariants();
+        for (block_position, length, left, right) in other.bands() {
+            if block_position + length != Au(i32::MAX) {
+                assert_eq!(zone.insets_at(at_block + block_position), (left, right));
+            }
+        }
+        zone.bands().eq(combined.bands())
+    }
+
+    fn check_place_centered(inline_size: InlineSize, exclusions: Vec<Exclusion>, object: Size)
+                            -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let placement = zone.place_centered(&object);
+        if object.inline > inline_size.0 {
+            return placement.origin.inline == Au(0)
+        }
+        let (inline_start, inline_end) = zone.available_run(placement.origin.block);
+        let slack = inline_end - inline_start - object.inline;
+        // Adding half the slack on both sides of the object, with the odd app unit on the right,
+        // spans the whole run.
+        placement.origin.block == zone.first_fit(object.inline).0 &&
+            placement.origin.inline - slack / 2 == inline_start &&
+            placement.origin.inline * 2 + object.inline + slack % 2 == inline_start + inline_end
+    }
+
+    fn check_subregion(inline_size: InlineSize,
+                       exclusions: Vec<Exclusion>,
+                       full_width: Option<(u8, u8)>,
+                       inline_range: (u8, u8),
+                       object: Size)
+                       -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        if let Some((block_start, block_size)) = full_width {
+            zone.exclude_full_width(Au(block_start as i32), Au(block_size as i32));
+        }
+        let range_start = cmp::min(Au(inline_range.0 as i32), inline_size.0);
+        let range_end = cmp::min(range_start + Au(inline_range.1 as i32), inline_size.0);
+        let mut subregion = zone.subregion((range_start, range_end));
+        subregion.assert_invariants();
+        assert_eq!(subregion.inline_size(), range_end - range_start);
+        for (block_position, _, _, _) in zone.bands() {
+            let (inline_start, inline_end) = zone.available_run(block_position);
+            let (inline_start, inline_end) = (cmp::max(inline_start, range_start),
+                                              cmp::min(inline_end, range_end));
+            if inline_start < inline_end {
+                assert_eq!(subregion.available_run(block_position), (inline_start, inline_end));
+            }
+        }
+        if object.inline < Au(0) || object.inline > range_end - range_start {
+            return true
+        }
+        [Side::Left, Side::Right].iter().all(|&side| {
+            let origin = subregion.place(side, &object).origin;
+            origin.inline >= range_start && origin.inline + object.inline <= range_end
+        })
+    }
+
+    fn check_first_fit(inline_size: InlineSize,
+                       exclusions: Vec<Exclusion>,
+                       full_width: Option<(u8, u8)>,
+                       object: Size)
+                       -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        if let Some((block_start, block_size)) = full_width {
+            zone.exclude_full_width(Au(block_start as i32), Au(block_size as i32));
+        }
+        let (block_position, available_inline_size) = zone.first_fit(object.inline);
+        [Side::Left, Side::Right].iter().all(|&side| {
+            let placement = zone.place(side, &object);
+            placement.origin.block == block_position &&
+                placement.available_inline_size == available_inline_size
+        })
+    }
+
+    fn check_would_overlap(inline_size: InlineSize,
+                           exclusions: Vec<Exclusion>,
+                           origin: (u16, u16),
+                           size: Exclusion)
+                           -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = self::exclusions(inline_size, &areas);
+        let candidate = ExcludedArea::new(&size,
+                                          &Point::new(Au(origin.0 as i32), Au(origin.1 as i32)));
+        let size = &candidate.exclusion.size;
+        if areas.iter().any(|area| {
+            area.exclusion.size.inline > Au(0) && area.exclusion.size.block > Au(0) &&
+                size.inline > Au(0) && size.block > Au(0) && area.intersects(&candidate)
+        }) {
+            assert!(zone.would_overlap(&candidate.origin, size))
+        }
+
+        let size = Size::new(cmp::min(size.inline, inline_size.0), size.block);
+        let placement = zone.place(candidate.exclusion.side, &size);
+        !zone.would_overlap(&placement.origin, &size)
+    }
+
+    fn check_inline_start(inline_size: InlineSize, exclusions: Vec<Exclusion>, offset: i16)
+                          -> bool {
+        let offset = Au(offset as i32);
+        let mut plain = Exclusions::new(inline_size.0);
+        let mut shifted = Exclusions::with_inline_start(inline_size.0, offset);
+        for exclusion in &exclusions {
+            for &side in &[Side::Left, Side::Right] {
+                let (plain_placement, shifted_placement) = (plain.place(side, &exclusion.size),
+                                                            shifted.place(side, &exclusion.size));
+                assert_eq!(shifted_placement.origin,
+                           Point::new(plain_placement.origin.inline + offset,
+                                      plain_placement.origin.block));
+                assert_eq!(shifted_placement.available_inline_size,
+                           plain_placement.available_inline_size);
+            }
+            let origin = shifted.place(exclusion.side, &exclusion.size).origin;
+            shifted.exclude_at(exclusion.side, &origin, &exclusion.size);
+            let origin = Point::new(origin.inline - offset, origin.block);
+            plain.exclude_at(exclusion.side, &origin, &exclusion.size);
+            assert!(shifted.bands().eq(plain.bands()));
+
+            let (plain_start, plain_end) = plain.available_run(origin.block);
+            assert_eq!(shifted.available_run(origin.block),
+                       (plain_start + offset, plain_end + offset));
+            assert_eq!(shifted.is_excluded(&Point::new(offset, origin.block)),
+                       plain.is_excluded(&Point::new(Au(0), origin.block)));
+        }
+        true
+    }
+
+    fn check_from_bands(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = self::exclusions(inline_size, &areas);
+        let bands: Vec<_> = zone.bands().collect();
+        let mut imported = Exclusions::from_bands(inline_size.0, bands.clone()).unwrap();
+        imported.assert_invariants();
+        assert_eq!(imported.bands().collect::<Vec<_>>(), bands);
+        for &side in &[Side::Left, Side::Right] {
+            assert_eq!(imported.clearance(side), zone.clearance(side));
+        }
+        imported.set_inline_size(inline_size.0);
+        zone.set_inline_size(inline_size.0);
+        imported.bands().eq(zone.bands())
+    }
+
+    fn check_place_around_intervals(inline_size: InlineSize,
+                                    exclusions: Vec<Exclusion>,
+                                    intervals: Vec<(u16, u16, u16, u16)>)
+                                    -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions.clone()));
+        for &(block_start, block_size, inline_start, inline_size) in &intervals {
+            let inline_start = Au(inline_start as i32);
+            zone.exclude_interval(Au(block_start as i32),
+                                  Au(block_size as i32),
+                                  (inline_start, inline_start + Au(inline_size as i32)));
+        }
+        for exclusion in &exclusions {
+            let size = Size::new(cmp::min(exclusion.size.inline, inline_size.0),
+                                 exclusion.size.block);
+            let placement = zone.place(exclusion.side, &size);
+            assert!(!zone.would_overlap(&placement.origin, &size));
+            for (run_start, run_end) in zone.runs_at(placement.origin.block) {
+                assert!(run_start < run_end);
+                assert!(!zone.is_excluded(&Point::new(run_start, placement.origin.block)));
+                assert!(zone.is_excluded(&Point::new(run_end, placement.origin.block)) ||
+                        run_end == inline_size.0);
+            }
+        }
+        true
+    }
+
+    fn check_equality_ignores_order(inline_size: InlineSize,
+                                    exclusions: Vec<Exclusion>,
+                                    swaps: Vec<usize>)
+                                    -> bool {
+        let mut areas = place(inline_size, exclusions);
+        let zone = self::exclusions(inline_size, &areas);
+        permute(&mut areas, &swaps);
+        let shuffled = self::exclusions(inline_size, &areas);
+        assert_eq!(zone, shuffled);
+        assert!(zone != Exclusions::new(inline_size.0 + Au(1)));
+        zone == zone.clone()
+    }
+
+    fn check_exclude_many(inline_size: InlineSize, exclusions: Vec<Exclusion>, swaps: Vec<usize>)
+                          -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut objects: Vec<_> = areas.iter().map(|area| {
+            (area.exclusion.side, area.origin, area.exclusion.size)
+        }).collect();
+        let mut batched = Exclusions::new(inline_size.0);
+        batched.exclude_many(&objects);
+        batched.assert_invariants();
+        let sequential = self::exclusions(inline_size, &areas);
+        assert!(batched.bands().eq(sequential.bands()));
+
+        // The bands don't depend on the order in which the objects are excluded.
+        permute(&mut objects, &swaps);
+        let mut permuted = Exclusions::new(inline_size.0);
+        permuted.exclude_many(&objects);
+        permuted.bands().eq(sequential.bands())
+    }
+
+    fn check_place_preferred(inline_size: InlineSize, exclusions: Vec<Exclusion>, size: Size)
+                             -> bool {
+        let mut exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        for &side in &[Side::Left, Side::Right] {
+            let (chosen_side, placement) = exclusions.place_preferred(side, &size);
+            assert_eq!(placement, exclusions.place(chosen_side, &size));
+            assert!(placement.origin.block <= exclusions.place(Side::Left, &size).origin.block);
+            assert!(placement.origin.block <= exclusions.place(Side::Right, &size).origin.block);
+            if chosen_side != side {
+                assert!(placement.origin.block < exclusions.place(side, &size).origin.block)
+            }
+        }
+        true
+    }
+
+    fn check_runs(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let (mut run_area, mut overlap_area, mut height) = (0, 0, Au(0));
+        for ((block_start, inline_start, inline_end), (band_block_start, length, left, right)) in
+                zone.runs().zip(zone.bands()) {
+            assert_eq!(block_start, band_block_start);
+            assert_eq!((inline_start, inline_end), zone.available_run(block_start));
+            if block_start + length == Au(i32::MAX) {
+                continue
+            }
+            run_area += (inline_end - inline_start).0 as i64 * length.0 as i64;
+            overlap_area += cmp::max(left + right - inline_size.0, Au(0)).0 as i64 *
+                length.0 as i64;
+            height = block_start + length;
+        }
+        assert_eq!(zone.runs().count(), zone.band_count());
+        run_area + zone.excluded_area() - overlap_area ==
+            (inline_size.0).0 as i64 * height.0 as i64
+    }
+
+    fn check_excluded_area(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let mut zone = Exclusions::new(inline_size.0);
+        let mut excluded_area = 0;
+        for area in &place(inline_size, exclusions) {
+            exclude(&mut zone, inline_size, area);
+            assert!(zone.excluded_area() >= excluded_area);
+            excluded_area = zone.excluded_area();
+            if let Some((block_start, block_end)) = zone.bounds() {
+                assert!(zone.insets_at(block_start) != (Au(0), Au(0)));
+                assert!(zone.insets_at(block_end - Au(1)) != (Au(0), Au(0)));
+                assert_eq!(zone.insets_at(block_end), (Au(0), Au(0)));
+            } else {
+                assert_eq!(excluded_area, 0)
+            }
+        }
+        true
+    }
+
+    fn check_from_floats(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = Exclusions::new(inline_size.0);
+        for area in &areas {
+            let placement = zone.place_and_exclude(area.exclusion.side, &area.exclusion.size);
+            zone.assert_invariants();
+            assert_eq!(placement.origin, area.origin);
+        }
+        assert!(zone.bands().eq(self::exclusions(inline_size, &areas).bands()));
+
+        let floats = areas.iter().map(|area| {
+            (area.exclusion.side, area.origin, area.exclusion.size)
+        });
+        Exclusions::from_floats(inline_size.0, floats).bands().eq(zone.bands())
+    }
+
+    fn check_place_all(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions);
+        let floats: Vec<_> = areas.iter().map(|area| {
+            (area.exclusion.side, area.exclusion.size)
+        }).collect();
+        let mut zone = Exclusions::new(inline_size.0);
+        let placements = zone.place_all(&floats);
+        assert!(zone.bands().eq(self::exclusions(inline_size, &areas).bands()));
+        placements.iter().zip(areas.iter()).all(|(placement, area)| {
+            placement.origin == area.origin
+        })
+    }
+
+    fn check_place_block(inline_size: InlineSize, exclusions: Vec<Exclusion>, object: Exclusion)
+                         -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let placement = zone.place_block(object.side, &object.size);
+        assert_eq!(placement, zone.place(object.side, &object.size));
+        object.size.inline > inline_size.0 ||
+            zone.available_width(placement.origin.block, object.size.block) >= object.size.inline
+    }
+
+    fn check_shrink_to_fit(inline_size: InlineSize, exclusions: Vec<Exclusion>, keep: Vec<bool>)
+                           -> bool {
+        let areas = place(inline_size, exclusions);
+        let mut zone = self::exclusions(inline_size, &areas);
+        for (i, area) in areas.iter().enumerate() {
+            if !keep.get(i).cloned().unwrap_or(true) {
+                zone.remove(area.exclusion.side, &area.origin, &area.exclusion.size);
+            }
+        }
+        let before = zone.clone();
+        let heap_bytes = zone.approx_heap_bytes();
+        zone.shrink_to_fit();
+        zone.assert_invariants();
+        assert!(zone.approx_heap_bytes() <= heap_bytes);
+        assert!(zone.bands().eq(before.bands()));
+        areas.iter().all(|area| {
+            [Side::Left, Side::Right].iter().all(|&side| {
+                zone.try_place(side, &area.exclusion.size) ==
+                    before.try_place(side, &area.exclusion.size)
+            })
+        })
+    }
+
+    fn check_pack(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let exclusions: Vec<_> = exclusions.into_iter().map(|mut exclusion| {
+            exclusion.size.inline = cmp::min(exclusion.size.inline, inline_size.0);
+            exclusion
+        }).collect();
+        let floats: Vec<_> = exclusions.iter().map(|exclusion| {
+            (exclusion.side, exclusion.size)
+        }).collect();
+        let mut zone = Exclusions::new(inline_size.0);
+        let placements = zone.pack(&floats);
+        zone.assert_invariants();
+        let areas: Vec<_> = exclusions.iter().zip(placements.iter()).map(|(exclusion, placement)| {
+            ExcludedArea::new(exclusion, &placement.origin)
+        }).collect();
+        for (i, a) in areas.iter().enumerate() {
+            assert!(a.origin.inline + a.exclusion.size.inline <= inline_size.0);
+            for b in &areas[(i + 1)..] {
+                assert!(!a.intersects(b))
+            }
+        }
+        true
+    }
+
+    fn check_undo_last_exclude(inline_size: InlineSize,
+                               exclusions: Vec<Exclusion>,
+                               full_width: Option<(u8, u8)>,
+                               last: Exclusion)
+                               -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        if let Some((block_start, block_size)) = full_width {
+            zone.exclude_full_width(Au(block_start as i32), Au(block_size as i32));
+            zone.assert_invariants();
+        }
+        let mut before = zone.clone();
+        zone.exclude(last.side, &last.size);
+        zone.assert_invariants();
+        assert_eq!(zone.undo_last_exclude(), last.size.inline > Au(0) && last.size.block > Au(0));
+        zone.assert_invariants();
+        assert!(!zone.undo_last_exclude());
+        assert!(zone.bands().eq(before.bands()));
+        assert_eq!(zone.band_count(), before.band_count());
+        for &side in &[Side::Left, Side::Right] {
+            assert_eq!(zone.clearance(side), before.clearance(side));
+            assert_eq!(zone.place(side, &last.size), before.place(side, &last.size));
+        }
+        true
+    }
+
+    fn check_snapshot_restore(inline_size: InlineSize,
+                              exclusions: Vec<Exclusion>,
+                              more_exclusions: Vec<Exclusion>,
+                              size: Size)
+                              -> bool {
+        let mut zone = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let before = zone.clone();
+        let snapshot = zone.snapshot();
+        for exclusion in &more_exclusions {
+            zone.place_and_exclude(exclusion.side, &exclusion.size);
+        }
+        zone.exclude_full_width(Au(10), Au(10));
+        zone.set_inline_size(inline_size.0 + Au(10));
+        zone.restore(snapshot);
+        zone.assert_invariants();
+        assert!(zone.bands().eq(before.bands()));
+        assert_eq!(zone.inline_size(), before.inline_size());
+        for &side in &[Side::Left, Side::Right] {
+            assert_eq!(zone.clearance(side), before.clearance(side));
+            assert_eq!(zone.try_place(side, &size), before.try_place(side, &size));
+        }
+        true
+    }
+
+    fn check_line_placements(inline_size: InlineSize,
+                             exclusions: Vec<Exclusion>,
+                             block_start: u16,
+                             line_height: u8)
+                             -> bool {
+        let exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let line_height = Au(line_height as i32 + 1);
+        let mut next_block_position = Au(block_start as i32);
+        for placement in exclusions.line_placements(next_block_position, line_height).take(20) {
+            assert!(placement.origin.block >= next_block_position);
+            assert!(placement.available_inline_size > Au(0));
+            let block_end = placement.origin.block + line_height;
+            for (band_block_start, length, left, right) in exclusions.bands() {
+                if band_block_start < block_end &&
+                        band_block_start + length > placement.origin.block {
+                    assert!(placement.origin.inline >= left);
+                    assert!(placement.origin.inline + placement.available_inline_size <=
+                            inline_size.0 - right);
+                }
+            }
+            next_block_position = block_end;
+        }
+        true
+    }
+
+    fn check_iterate_by_reference(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let exclusions = self::exclusions(inline_size, &place(inline_size, exclusions));
+        let first: Vec<_> = (&exclusions).into_iter().collect();
+        let mut second = vec![];
+        for band in &exclusions {
+            second.push(band);
+        }
+        assert_eq!(first, second);
+        assert_eq!(first, exclusions.bands().collect::<Vec<_>>());
+
+        let map: SplayMap<Au, Au> = first.iter().map(|band| (band.0, band.1)).collect();
+        let first: Vec<_> = (&map).into_iter().collect();
+        let mut second = vec![];
+        for (key, value) in &map {
+            second.push((key, value));
+        }
+        first == second
+    }
+}
+
+#[test]
+fn test_clear() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(50), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(40)));
+    exclusions.clear();
+    assert_eq!(exclusions.inline_size(), Au(100));
+
+    // The bands are back to the single infinitely long one, but the list of excluded areas keeps
+    // its room.
+    let mut fresh = Exclusions::new(Au(100));
+    assert!(exclusions.bands().eq(fresh.bands()));
+    assert!(exclusions.approx_heap_bytes() > fresh.approx_heap_bytes());
+    for &side in &[Side::Left, Side::Right] {
+        for &size in &[Size::new(Au(10), Au(10)), Size::new(Au(100), Au(5))] {
+            assert_eq!(exclusions.place(side, &size), fresh.place(side, &size))
+        }
+    }
+}
+
+#[test]
+fn test_insets_at() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(15), Au(20)));
+
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(30), Au(15)));
+    assert_eq!(exclusions.insets_at(Au(9)), (Au(30), Au(15)));
+    assert_eq!(exclusions.insets_at(Au(10)), (Au(20), Au(15)));
+    assert_eq!(exclusions.insets_at(Au(20)), (Au(10), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(29)), (Au(10), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(30)), (Au(0), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(i32::MAX)), (Au(0), Au(0)));
+}
+
+#[test]
+fn test_place_block() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(20)));
+    exclusions.exclude_interval(Au(40), Au(10), (Au(70), Au(90)));
+    let placement = exclusions.place_block(Side::Right, &Size::new(Au(40), Au(30)));
+    assert_eq!(placement.origin, Point::new(Au(60), Au(0)));
+    let placement = exclusions.place_block(Side::Left, &Size::new(Au(50), Au(30)));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(20)));
+
+    // A short box fits above the rectangle in the middle of the zone, but a tall one doesn't.
+    let placement = exclusions.place_block(Side::Right, &Size::new(Au(40), Au(40)));
+    assert_eq!(placement.origin, Point::new(Au(60), Au(0)));
+    let placement = exclusions.place_block(Side::Right, &Size::new(Au(40), Au(41)));
+    assert_eq!(placement.origin, Point::new(Au(60), Au(50)));
+}
+
+#[test]
+fn test_place_block_replaced_element() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude_interval(Au(20), Au(10), (Au(60), Au(100)));
+
+    // A short image fits in the first band, but a tall one would reach the rectangle below it.
+    let placement = exclusions.place_block(Side::Right, &Size::new(Au(60), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(40), Au(0)));
+    let image = Size::new(Au(60), Au(25));
+    let placement = exclusions.place_block(Side::Right, &image);
+    assert_eq!(placement.origin, Point::new(Au(40), Au(30)));
+    assert!(!exclusions.would_overlap(&placement.origin, &image));
+    exclusions.exclude_at(Side::Right, &placement.origin, &image);
+    assert_eq!(exclusions.insets_at(Au(50)), (Au(0), Au(60)));
+}
+
+#[test]
+fn test_pack() {
+    fn height(floats: &[(Side, Size)], placements: &[Placement]) -> Au {
+        floats.iter().zip(placements.iter()).map(|(float, placement)| {
+            placement.origin.block + float.1.block
+        }).max().unwrap()
+    }
+
+    // In document order, the wide float goes below the narrow one and pushes the last float down
+    // beneath it. Packing places the last float beside the first one instead.
+    let floats = [(Side::Left, Size::new(Au(20), Au(10))),
+                  (Side::Left, Size::new(Au(90), Au(10))),
+                  (Side::Left, Size::new(Au(70), Au(10)))];
+    let placements = Exclusions::new(Au(100)).place_all(&floats);
+    assert_eq!(height(&floats, &placements), Au(30));
+    let mut exclusions = Exclusions::new(Au(100));
+    let placements = exclusions.pack(&floats);
+    assert_eq!(placements.iter().map(|placement| placement.origin).collect::<Vec<_>>(),
+               vec![Point::new(Au(0), Au(0)),
+                    Point::new(Au(0), Au(10)),
+                    Point::new(Au(20), Au(0))]);
+    assert_eq!(height(&floats, &placements), Au(20));
+    assert!(Exclusions::new(Au(100)).pack(&[]).is_empty());
+}
+
+#[test]
+fn test_band_at() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    for (block_position, length, left, right) in exclusions.bands() {
+        let band = exclusions.band_at(block_position);
+        assert_eq!((band.length(), band.left(), band.right()), (length, left, right));
+        assert_eq!(exclusions.insets_at(block_position), (band.left(), band.right()));
+    }
+    assert_eq!(exclusions.band_at(Au(10)), exclusions.band_at(Au(19)));
+    assert_eq!(exclusions.band_at(Au(20)).length(), Au(i32::MAX - 20));
+}
+
+#[test]
+fn test_exclude_span() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_span(Side::Left, Au(40), Au(100), Au(150));
+    exclusions.assert_invariants();
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(100), Au(0), Au(0)),
+                    (Au(100), Au(50), Au(40), Au(0)),
+                    (Au(150), Au(i32::MAX - 150), Au(0), Au(0))]);
+    assert_eq!(exclusions.clearance(Side::Left), Au(150));
+
+    // Nothing goes above the float, even though there is room there.
+    let placement = exclusions.place(Side::Left, &Size::new(Au(50), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(40), Au(100)));
+    let placement = exclusions.place(Side::Right, &Size::new(Au(70), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(30), Au(150)));
+
+    exclusions.exclude_span(Side::Right, Au(20), Au(120), Au(130));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(110)));
+    exclusions.assert_invariants();
+    assert_eq!(exclusions.insets_at(Au(50)), (Au(10), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(125)), (Au(40), Au(20)));
+    let bands: Vec<_> = exclusions.bands().collect();
+    exclusions.set_inline_size(Au(100));
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(), bands);
 }
 
+#[test]
+fn test_exclude_merges_across_range_edges() {
+    // Excluding a range whose insets match the band just below or just above it leaves a single
+    // band either way, so the bands don't depend on the order of the exclusions.
+    let mut below_first = Exclusions::new(Au(100));
+    below_first.exclude_span(Side::Left, Au(40), Au(50), Au(100));
+    below_first.exclude_span(Side::Left, Au(40), Au(20), Au(50));
+    let mut above_first = Exclusions::new(Au(100));
+    above_first.exclude_span(Side::Left, Au(40), Au(20), Au(50));
+    above_first.exclude_span(Side::Left, Au(40), Au(50), Au(100));
+    for exclusions in &[below_first, above_first] {
+        exclusions.assert_invariants();
+        assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+                   vec![(Au(0), Au(20), Au(0), Au(0)),
+                        (Au(20), Au(80), Au(40), Au(0)),
+                        (Au(100), Au(i32::MAX - 100), Au(0), Au(0))]);
+    }
+}
+
+#[test]
+fn test_exclude_zero_block_size() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    let before = exclusions.clone();
+    exclusions.exclude(Side::Right, &Size::new(Au(50), Au(0)));
+    assert!(!exclusions.undo_last_exclude());
+    exclusions.exclude_span(Side::Left, Au(60), Au(50), Au(50));
+    exclusions.exclude_full_width(Au(40), Au(0));
+    exclusions.assert_invariants();
+    assert!(exclusions == before);
+    assert_eq!(exclusions.clearance(Side::Right), Au(0));
+    let placement = exclusions.place(Side::Right, &Size::new(Au(70), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(30), Au(0)));
+
+    // A placed float with no block size still excludes the area above it, which reaches up to
+    // the top of the zone.
+    exclusions.exclude_at(Side::Left, &Point::new(Au(30), Au(10)), &Size::new(Au(40), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(70), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(10)), (Au(30), Au(0)));
+}
+
+#[test]
+fn test_translate_block() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    exclusions.translate_block(Au(50));
+    exclusions.assert_invariants();
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(50), Au(0), Au(0)),
+                    (Au(50), Au(10), Au(30), Au(20)),
+                    (Au(60), Au(10), Au(30), Au(0)),
+                    (Au(70), Au(i32::MAX - 70), Au(0), Au(0))]);
+    let placement = exclusions.place(Side::Left, &Size::new(Au(10), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(30), Au(50)));
+    assert_eq!(exclusions.clearance(Side::Left), Au(70));
+
+    assert!(exclusions.remove(Side::Left, &Point::new(Au(0), Au(50)), &Size::new(Au(30), Au(20))));
+    assert_eq!(exclusions.insets_at(Au(55)), (Au(0), Au(20)));
+    assert_eq!(exclusions.insets_at(Au(65)), (Au(0), Au(0)));
+
+    exclusions.translate_block(Au(-55));
+    exclusions.assert_invariants();
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(5), Au(0), Au(20)), (Au(5), Au(i32::MAX - 5), Au(0), Au(0))]);
+    exclusions.translate_block(Au(-5));
+    assert_eq!(exclusions.band_count(), 1);
+    let placement = exclusions.place(Side::Left, &Size::new(Au(100), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(0)));
+}
+
+#[test]
+fn test_append() {
+    let mut first = Exclusions::new(Au(100));
+    first.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    first.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    let mut second = Exclusions::new(Au(100));
+    second.exclude(Side::Left, &Size::new(Au(30), Au(15)));
+
+    assert_eq!(first.append(Exclusions::new(Au(50)), Au(20)), Err(MismatchedZones));
+    let rtl = Exclusions::with_direction(Au(100), Direction::Rtl);
+    assert_eq!(first.append(rtl, Au(20)), Err(MismatchedZones));
+    assert_eq!(first.band_count(), 3);
+
+    // The insets match where the zones meet, so the bands there are merged.
+    first.append(second, Au(20)).unwrap();
+    first.assert_invariants();
+    assert_eq!(first.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(10), Au(30), Au(20)),
+                    (Au(10), Au(25), Au(30), Au(0)),
+                    (Au(35), Au(i32::MAX - 35), Au(0), Au(0))]);
+    let placement = first.place(Side::Left, &Size::new(Au(50), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(30), Au(20)));
+}
+
+#[test]
+fn test_place_preferred() {
+    // The rectangle on the right pushes an object aligned to the right below it, so the left
+    // side is chosen.
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_interval(Au(0), Au(20), (Au(60), Au(100)));
+    let size = Size::new(Au(30), Au(10));
+    assert_eq!(exclusions.place(Side::Right, &size).origin, Point::new(Au(70), Au(20)));
+    let (side, placement) = exclusions.place_preferred(Side::Right, &size);
+    assert_eq!((side, placement.origin), (Side::Left, Point::new(Au(0), Au(0))));
+
+    // Here both sides place the object at the top, on either side of the rectangle, and the
+    // preferred side wins.
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_interval(Au(0), Au(20), (Au(40), Au(60)));
+    let (side, placement) = exclusions.place_preferred(Side::Right, &size);
+    assert_eq!((side, placement.origin), (Side::Right, Point::new(Au(70), Au(0))));
+    let (side, placement) = exclusions.place_preferred(Side::Left, &size);
+    assert_eq!((side, placement.origin), (Side::Left, Point::new(Au(0), Au(0))));
+}
+
+#[test]
+fn test_place_centered() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(70), Au(5)));
+    let placement = exclusions.place_centered(&Size::new(Au(30), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(45), Au(5)));
+    assert_eq!(placement.available_inline_size, Au(80));
+
+    // The odd app unit of slack goes on the right.
+    let placement = exclusions.place_centered(&Size::new(Au(29), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(45), Au(5)));
+    let placement = exclusions.place_centered(&Size::new(Au(100), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(10)));
+    let placement = exclusions.place_centered(&Size::new(Au(150), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(10)));
+
+    let mut exclusions = Exclusions::with_inline_start(Au(100), Au(1000));
+    let placement = exclusions.place_centered(&Size::new(Au(50), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(1025), Au(0)));
+}
+
+#[test]
+fn test_subregion() {
+    let mut exclusions = Exclusions::with_inline_start(Au(100), Au(1000));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    exclusions.exclude(Side::Left, &Size::new(Au(40), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(20)));
+    exclusions.exclude_interval(Au(40), Au(10), (Au(1050), Au(1090)));
+
+    let mut subregion = exclusions.subregion((Au(1020), Au(1080)));
+    assert_eq!((subregion.inline_start(), subregion.inline_size()), (Au(1020), Au(60)));
+    assert_eq!(subregion.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(10), Au(20), Au(10)),
+                    (Au(10), Au(10), Au(0), Au(10)),
+                    (Au(20), Au(i32::MAX - 20), Au(0), Au(0))]);
+    assert_eq!(subregion.runs_at(Au(45)), vec![(Au(1020), Au(1050))]);
+    let placement = subregion.place(Side::Right, &Size::new(Au(50), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(1020), Au(10)));
+
+    // Asking for more than the zone covers gets only the zone.
+    let subregion = exclusions.subregion((Au(900), Au(1200)));
+    assert_eq!((subregion.inline_start(), subregion.inline_size()), (Au(1000), Au(100)));
+    assert!(subregion.bands().eq(exclusions.bands()));
+
+    // Barriers apply across the whole inline size, so they are carried over as they are.
+    exclusions.set_barrier(Au(70));
+    assert_eq!(exclusions.subregion((Au(1020), Au(1080))).barrier(), Some(Au(70)));
+}
+
+#[test]
+fn test_try_new() {
+    assert_eq!(Exclusions::try_new(Au(-5)).err(), Some(NegativeInlineSize));
+    assert_eq!(Exclusions::try_new(Au(0)).ok(), Some(Exclusions::new(Au(0))));
+    let exclusions = Exclusions::try_new(Au(100)).unwrap();
+    assert_eq!(exclusions.inline_size(), Au(100));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn test_new_rejects_negative_inline_size() {
+    Exclusions::new(Au(-5));
+}
+
+#[test]
+fn test_tree_depth() {
+    let mut exclusions = Exclusions::new(Au(1000));
+    assert_eq!(exclusions.tree_depth(), (1, 1.0));
+    for index in 0..100 {
+        exclusions.exclude(Side::Left, &Size::new(Au(1000 - index * 10), Au(index * 10 + 10)));
+    }
+    assert_eq!(exclusions.band_count(), 101);
+    exclusions.compact();
+    assert_eq!(exclusions.tree_depth().0, 7);
+}
+
+#[test]
+fn test_barrier() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(30)));
+    assert_eq!(exclusions.barrier(), None);
+    exclusions.set_barrier(Au(50));
+    exclusions.set_barrier(Au(40));
+    assert_eq!(exclusions.barrier(), Some(Au(40)));
+
+    // A float too wide to go beside the exclusion would be pushed down past the barrier.
+    assert_eq!(exclusions.try_place(Side::Left, &Size::new(Au(50), Au(20))), None);
+    assert_eq!(exclusions.try_place_block(Side::Left, &Size::new(Au(50), Au(20))), None);
+    let placement = exclusions.try_place(Side::Left, &Size::new(Au(50), Au(10))).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(0), Au(30)));
+    let placement = exclusions.try_place(Side::Right, &Size::new(Au(40), Au(40))).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(60), Au(0)));
+    assert_eq!(exclusions.try_place(Side::Right, &Size::new(Au(40), Au(41))), None);
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(50), Au(20))).origin,
+               Point::new(Au(0), Au(30)));
+
+    // Clearing the inner barrier leaves the outer one in effect.
+    assert_eq!(exclusions.clear_barrier(), Some(Au(40)));
+    assert!(exclusions.try_place(Side::Left, &Size::new(Au(50), Au(20))).is_some());
+    assert_eq!(exclusions.try_place(Side::Left, &Size::new(Au(50), Au(21))), None);
+    assert_eq!(exclusions.clear_barrier(), Some(Au(50)));
+    assert_eq!(exclusions.clear_barrier(), None);
+    assert!(exclusions.try_place(Side::Left, &Size::new(Au(50), Au(21))).is_some());
+}
+
+#[test]
+fn test_width_breakpoints() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.width_breakpoints(), vec![(Au(0), Au(100))]);
+    exclusions.exclude(Side::Left, &Size::new(Au(40), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(70), Au(5)));
+    exclusions.exclude(Side::Right, &Size::new(Au(60), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(20)));
+    exclusions.exclude_span(Side::Right, Au(30), Au(20), Au(30));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+
+    // The exclusions overlap in both of the first two bands, so neither has any room, and the
+    // next two bands are as wide as each other.
+    assert_eq!(exclusions.band_count(), 5);
+    assert_eq!(exclusions.width_breakpoints(),
+               vec![(Au(0), Au(0)), (Au(10), Au(60)), (Au(30), Au(100))]);
+}
+
+#[test]
+fn test_is_empty() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert!(exclusions.is_empty());
+    exclusions.exclude(Side::Left, &Size::new(Au(0), Au(10)));
+    assert!(exclusions.is_empty());
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    assert!(!exclusions.is_empty());
+    exclusions.clear();
+    assert!(exclusions.is_empty());
+    exclusions.exclude_interval(Au(10), Au(10), (Au(40), Au(60)));
+    assert!(!exclusions.is_empty());
+
+    // Nothing is excluded in a zone with no inline size, but objects are still pushed down.
+    let mut exclusions = Exclusions::new(Au(0));
+    exclusions.exclude_full_width(Au(0), Au(10));
+    assert_eq!(exclusions.band_count(), 1);
+    assert!(!exclusions.is_empty());
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_shape(Side::Left, &Point::new(Au(-50), Au(0)),
+                             &[(Au(0), Au(0)), (Au(20), Au(0)), (Au(20), Au(10))]);
+    assert_eq!(exclusions.band_count(), 1);
+    assert!(!exclusions.is_empty());
+}
+
+#[test]
+fn test_exclusions_builder() {
+    assert_eq!(ExclusionsBuilder::new().inline_size(Au(100)).build(),
+               Ok(Exclusions::new(Au(100))));
+    assert_eq!(ExclusionsBuilder::default().build(), Ok(Exclusions::default()));
+
+    let mut exclusions = ExclusionsBuilder::new().inline_size(Au(100))
+                                                 .direction(Direction::Rtl)
+                                                 .writing_mode(WritingMode::VerticalRl)
+                                                 .inline_start(Au(10))
+                                                 .max_block(Some(Au(50)))
+                                                 .build()
+                                                 .unwrap();
+    assert_eq!((exclusions.inline_size(), exclusions.inline_start()), (Au(100), Au(10)));
+    assert_eq!(exclusions.direction(), Direction::Rtl);
+    assert_eq!(exclusions.writing_mode(), WritingMode::VerticalRl);
+    assert_eq!(exclusions.max_block(), Some(Au(50)));
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(30), Au(10))).origin,
+               Point::new(Au(80), Au(0)));
+    assert_eq!(exclusions.try_place(Side::Left, &Size::new(Au(30), Au(60))), None);
+
+    assert_eq!(ExclusionsBuilder::new().inline_size(Au(-1)).build(),
+               Err(BuildError::NegativeInlineSize));
+    assert_eq!(ExclusionsBuilder::new().max_block(Some(Au(-1))).build(),
+               Err(BuildError::NegativeMaxBlock));
+}
+
+#[test]
+fn test_render_ascii() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.render_ascii(4, 2), "....\n....\n");
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    assert_eq!(exclusions.render_ascii(10, 2), "#####.....\n#####.....\n");
+
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(10)));
+    assert_eq!(exclusions.render_ascii(10, 4),
+               "#####..###\n#####..###\n#####.....\n#####.....\n");
+    assert_eq!(exclusions.render_svg(),
+               "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"20\">\n\
+                \x20 <rect x=\"0\" y=\"0\" width=\"50\" height=\"10\"/>\n\
+                \x20 <rect x=\"70\" y=\"0\" width=\"30\" height=\"10\"/>\n\
+                \x20 <rect x=\"0\" y=\"10\" width=\"50\" height=\"10\"/>\n\
+                </svg>\n");
+}
+
+#[test]
+fn test_writing_mode() {
+    let container = Size::new(Au(100), Au(200));
+    let (origin, size) = (Point::new(Au(10), Au(20)), Size::new(Au(30), Au(40)));
+    let far_corner = Point::new(origin.inline + size.inline, origin.block + size.block);
+    let physical_rect = |writing_mode: WritingMode| {
+        let (x0, y0) = writing_mode.logical_to_physical(&origin, &container);
+        let (x1, y1) = writing_mode.logical_to_physical(&far_corner, &container);
+        assert_eq!(writing_mode.physical_to_logical((x0, y0), &container), origin);
+        assert_eq!(writing_mode.physical_to_logical((x1, y1), &container), far_corner);
+        let (x, y) = (cmp::min(x0, x1), cmp::min(y0, y1));
+        (x, y, cmp::max(x0, x1) - x, cmp::max(y0, y1) - y)
+    };
+    assert_eq!(physical_rect(WritingMode::HorizontalTb), (Au(10), Au(20), Au(30), Au(40)));
+    assert_eq!(physical_rect(WritingMode::VerticalRl), (Au(140), Au(10), Au(40), Au(30)));
+    assert_eq!(physical_rect(WritingMode::VerticalLr), (Au(20), Au(10), Au(40), Au(30)));
+    assert_eq!(physical_rect(WritingMode::SidewaysLr), (Au(20), Au(60), Au(40), Au(30)));
+
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.writing_mode(), WritingMode::HorizontalTb);
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    let bands: Vec<_> = exclusions.bands().collect();
+    exclusions.set_writing_mode(WritingMode::VerticalRl);
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(), bands);
+    let placement = exclusions.place(Side::Left, &Size::new(Au(30), Au(40)));
+    assert_eq!(exclusions.to_physical(&placement.origin, &container), (Au(200), Au(50)));
+}
+
+#[test]
+fn test_max_block() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(30)));
+    exclusions.set_max_block(Some(Au(50)));
+    assert_eq!(exclusions.max_block(), Some(Au(50)));
+
+    let size = Size::new(Au(40), Au(50));
+    assert_eq!(exclusions.try_place(Side::Left, &size).unwrap().origin,
+               Point::new(Au(60), Au(0)));
+    assert_eq!(exclusions.try_place_block(Side::Left, &size).unwrap().origin,
+               Point::new(Au(60), Au(0)));
+    let size = Size::new(Au(40), Au(51));
+    assert_eq!(exclusions.try_place(Side::Left, &size), None);
+    assert_eq!(exclusions.try_place_block(Side::Left, &size), None);
+
+    // A float too wide to go beside the exclusion goes below it, past the bottom of the container.
+    let size = Size::new(Au(50), Au(21));
+    assert_eq!(exclusions.try_place(Side::Right, &size), None);
+    assert_eq!(exclusions.place(Side::Right, &size).origin, Point::new(Au(50), Au(30)));
+
+    exclusions.set_max_block(None);
+    assert!(exclusions.try_place(Side::Right, &size).is_some());
+}
+
+#[test]
+fn test_band_side() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    exclusions.exclude_full_width(Au(40), Au(10));
+    assert_eq!(exclusions.band_side(Au(0)), None);
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(30), Au(20)));
+    assert_eq!(exclusions.band_side(Au(10)), Some(Side::Left));
+    assert_eq!(exclusions.band_side(Au(19)), Some(Side::Left));
+    assert_eq!(exclusions.band_side(Au(20)), None);
+    assert_eq!(exclusions.band_side(Au(40)), None);
+    assert_eq!(exclusions.insets_at(Au(40)), (Au(100), Au(0)));
+    assert_eq!(exclusions.band_side(Au(50)), None);
+
+    let mut exclusions = Exclusions::with_direction(Au(100), Direction::Rtl);
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    assert_eq!(exclusions.band_side(Au(0)), None);
+    assert_eq!(exclusions.band_side(Au(10)), Some(Side::Right));
+    assert_eq!(exclusions.insets_at(Au(10)), (Au(30), Au(0)));
+
+    // In a right-to-left zone, a full-width exclusion is stored as a right inset.
+    exclusions.exclude_full_width(Au(40), Au(10));
+    assert_eq!(exclusions.insets_at(Au(40)), (Au(0), Au(100)));
+    assert_eq!(exclusions.band_side(Au(40)), None);
+}
+
+#[test]
+fn test_place_below_float_near_max_block() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_many(&[(Side::Left, Point::new(Au(0), Au(i32::MAX - 10)),
+                               Size::new(Au(60), Au(5)))]);
+    assert_eq!(exclusions.band_count(), 2);
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(30), Au(3))).origin,
+               Point::new(Au(60), Au(0)));
+    assert_eq!(exclusions.place(Side::Right, &Size::new(Au(50), Au(20))).origin,
+               Point::new(Au(50), Au(i32::MAX - 5)));
+
+    // A float reaching past the end of the zone is clipped so that it can't be mistaken for the
+    // final band.
+    exclusions.exclude_many(&[(Side::Right, Point::new(Au(50), Au(i32::MAX - 3)),
+                               Size::new(Au(50), Au(10)))]);
+    assert_eq!(exclusions.bands().last(),
+               Some((Au(i32::MAX - 1), Au(1), Au(0), Au(0))));
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(50), Au(20))).origin,
+               Point::new(Au(0), Au(i32::MAX - 5)));
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(60), Au(20))).origin,
+               Point::new(Au(0), Au(i32::MAX - 1)));
+}
+
+#[test]
+fn test_clearance() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.clearance(Side::Left), Au(0));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(50)));
+    exclusions.exclude(Side::Left, &Size::new(Au(40), Au(30)));
+    assert_eq!(exclusions.clearance(Side::Left), Au(50));
+    assert_eq!(exclusions.clearance(Side::Right), Au(0));
+}
+
+#[test]
+fn test_min_block_for_clear() {
+    for &direction in &[Direction::Ltr, Direction::Rtl] {
+        let mut exclusions = Exclusions::with_direction(Au(100), direction);
+        exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+        exclusions.exclude(Side::Right, &Size::new(Au(30), Au(25)));
+        exclusions.exclude(Side::Left, &Size::new(Au(10), Au(15)));
+        assert_eq!(exclusions.min_block_for_clear(Clear::None), Au(0));
+        assert_eq!(exclusions.min_block_for_clear(Clear::Left), Au(15));
+        assert_eq!(exclusions.min_block_for_clear(Clear::Right), Au(25));
+        assert_eq!(exclusions.min_block_for_clear(Clear::Both), Au(25));
+
+        let size = Size::new(Au(10), Au(10));
+        for &clear in &[Clear::None, Clear::Left, Clear::Right, Clear::Both] {
+            let placement = exclusions.place_with_clear(Side::Left, clear, &size);
+            assert!(placement.origin.block >= exclusions.min_block_for_clear(clear));
+        }
+    }
+}
+
+#[test]
+fn test_margins() {
+    let size = Size::new(Au(10), Au(10));
+    let margins = LogicalMargins::new(Au(5), Au(5), Au(2), Au(0));
+    let mut exclusions = Exclusions::new(Au(100));
+    let placement = exclusions.place_with_margins(Side::Left, &size, &margins);
+    assert_eq!(placement.origin, Point::new(Au(5), Au(2)));
+    exclusions.exclude_with_margins(Side::Left, &size, &margins);
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(20), Au(0)));
+    assert_eq!(exclusions.place(Side::Left, &size).origin, Point::new(Au(20), Au(0)));
+
+    let placement = exclusions.place_with_margins(Side::Right, &size, &margins);
+    assert_eq!(placement.origin, Point::new(Au(85), Au(2)));
+}
+
+#[test]
+fn test_negative_margins() {
+    let size = Size::new(Au(10), Au(10));
+    let margins = LogicalMargins::new(Au(0), Au(-5), Au(0), Au(0));
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude_with_margins(Side::Left, &size, &margins);
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(5), Au(0)));
+
+    let margins = LogicalMargins::new(Au(-20), Au(0), Au(0), Au(0));
+    let placement = exclusions.place_with_margins(Side::Left, &size, &margins);
+    assert_eq!(placement.origin, Point::new(Au(-15), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(95));
+
+    // The margin box has a negative inline size, and the border box is flush with the right edge.
+    let placement = exclusions.place_with_margins(Side::Right, &size, &margins);
+    assert_eq!(placement.origin, Point::new(Au(90), Au(0)));
+    exclusions.exclude_with_margins(Side::Right, &size, &margins);
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(5), Au(0)));
+}
+
+#[test]
+fn test_place_below() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(80), Au(10)));
+
+    let size = Size::new(Au(40), Au(10));
+    assert_eq!(exclusions.place(Side::Left, &size).origin, Point::new(Au(50), Au(10)));
+    let placement = exclusions.place_below(Side::Left, &size, Au(15));
+    assert_eq!(placement.origin, Point::new(Au(50), Au(15)));
+    let placement = exclusions.place_below(Side::Left, &size, Au(20));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(20)));
+    assert_eq!(placement.available_inline_size, Au(100));
+}
+
+#[test]
+fn test_exclude_full_width() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude_full_width(Au(40), Au(10));
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(), vec![
+        (Au(0), Au(20), Au(30), Au(0)),
+        (Au(20), Au(20), Au(0), Au(0)),
+        (Au(40), Au(10), Au(100), Au(0)),
+        (Au(50), Au(i32::MAX - 50), Au(0), Au(0)),
+    ]);
+
+    // There is room above the excluded range, but objects must go below it.
+    let size = Size::new(Au(50), Au(10));
+    assert_eq!(exclusions.place(Side::Left, &size).origin, Point::new(Au(0), Au(50)));
+    assert_eq!(exclusions.place(Side::Right, &size).origin, Point::new(Au(50), Au(50)));
+
+    // Areas excluded from the top still reach past the full-width range.
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(60)));
+    assert_eq!(exclusions.insets_at(Au(10)), (Au(30), Au(20)));
+    assert_eq!(exclusions.insets_at(Au(30)), (Au(0), Au(20)));
+    assert_eq!(exclusions.insets_at(Au(55)), (Au(0), Au(20)));
+    assert_eq!(exclusions.place(Side::Left, &size).origin, Point::new(Au(0), Au(50)));
+
+    exclusions.set_inline_size(Au(200));
+    assert_eq!(exclusions.insets_at(Au(45)), (Au(200), Au(20)));
+}
+
+#[test]
+fn test_overlapping_floats() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(80), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(60), Au(10)));
+    assert_eq!(exclusions.available_run(Au(0)), (Au(80), Au(80)));
+
+    // Only an object with a negative inline size can be placed beside floats that overlap.
+    let placement = exclusions.place(Side::Left, &Size::new(Au(-50), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(80), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(0));
+    let placement = exclusions.place(Side::Left, &Size::new(Au(10), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(0), Au(10)));
+    assert_eq!(placement.available_inline_size, Au(100));
+}
+
+#[test]
+fn test_oversized_exclusion() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(150), Au(10)));
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(100), Au(0)));
+    let placement = exclusions.place(Side::Right, &Size::new(Au(10), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(90), Au(10)));
+
+    exclusions.set_inline_size(Au(200));
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(150), Au(0)));
+    assert!(exclusions.remove(Side::Left, &Point::new(Au(0), Au(0)), &Size::new(Au(150), Au(10))));
+    assert_eq!(exclusions.band_count(), 1);
+}
+
+#[test]
+fn test_zero_inline_size() {
+    let mut exclusions = Exclusions::new(Au(0));
+    let mut next_block_position = Au(0);
+    for &side in &[Side::Left, Side::Right, Side::Left] {
+        let size = Size::new(Au(10), Au(20));
+        let placement = exclusions.place(side, &size);
+        assert_eq!(placement.origin.block, next_block_position);
+        assert_eq!(placement.available_inline_size, Au(0));
+        next_block_position = placement.origin.block + size.block;
+        exclusions.exclude(side, &Size::new(size.inline, next_block_position));
+    }
+    assert_eq!(exclusions.clearance(Side::Left), Au(60));
+    assert_eq!(exclusions.place_line(Au(0), Au(10), Au(0)).unwrap().origin.block, Au(0));
+    assert!(exclusions.try_place(Side::Left, &Size::new(Au(10), Au(10))).is_none());
+    assert_eq!(exclusions.try_place(Side::Left, &Size::new(Au(0), Au(10))).unwrap().origin,
+               Point::new(Au(0), Au(0)));
+}
+
+#[test]
+fn test_place_wider_than_zone() {
+    // In a zone with no inline size, every exclusion leaves the bands as they were, so only the
+    // exclusions themselves say how far down an object that doesn't fit must go.
+    let mut exclusions = Exclusions::new(Au(0));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(50)));
+    assert_eq!(exclusions.band_count(), 1);
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(10), Au(5))).origin.block, Au(50));
+    assert_eq!(exclusions.first_fit(Au(10)).0, Au(50));
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(0), Au(5))).origin.block, Au(0));
+
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(70), Au(40)));
+    assert_eq!(exclusions.place(Side::Right, &Size::new(Au(150), Au(5))).origin,
+               Point::new(Au(-50), Au(40)));
+}
+
+#[test]
+fn test_with_capacity() {
+    // Only the list of excluded areas is reserved, so it is what makes the difference here.
+    let mut exclusions = Exclusions::with_capacity(Au(100), 10);
+    let mut fresh = Exclusions::new(Au(100));
+    assert!(exclusions.approx_heap_bytes() - fresh.approx_heap_bytes() >=
+            10 * mem::size_of::<(Side, Size)>());
+    for i in 0..10 {
+        let size = Size::new(Au(10), Au(i * 10 + 10));
+        exclusions.exclude(Side::Left, &size);
+        fresh.exclude(Side::Left, &size);
+    }
+    assert!(exclusions.bands().eq(fresh.bands()));
+}
+
+#[test]
+fn test_debug() {
+    let mut exclusions = Exclusions::new(Au::from_px(100));
+    exclusions.exclude(Side::Left, &Size::new(Au::from_px(30), Au::from_px(10)));
+    let debug = format!("{:?}", exclusions);
+    let lines: Vec<_> = debug.lines().collect();
+    assert_eq!(lines.len(), exclusions.band_count() + 1);
+    assert_eq!(lines[0], "Exclusions(inline_size=100px): bands:");
+    assert_eq!(lines[1], "    0px Band { left: -30px, right: 0px, length: 10px }");
+    assert!(lines[2].starts_with("    10px Band { left: 0px, right: 0px, length: "));
+}
+
+#[test]
+fn test_exclude_shape() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(5), Au(40)));
+
+    // A right triangle whose hypotenuse runs from (60, 10) down to (20, 50).
+    let triangle = [(Au(0), Au(0)), (Au(60), Au(0)), (Au(0), Au(60))];
+    exclusions.exclude_shape(Side::Left, &Point::new(Au(0), Au(10)), &triangle[..]);
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(0), Au(10)));
+    assert_eq!(exclusions.insets_at(Au(10)), (Au(60), Au(10)));
+    assert_eq!(exclusions.insets_at(Au(20)), (Au(50), Au(5)));
+    assert_eq!(exclusions.insets_at(Au(40)), (Au(30), Au(0)));
+    assert_eq!(exclusions.insets_at(Au(70)), (Au(0), Au(0)));
+    assert_eq!(exclusions.clearance(Side::Left), Au(70));
+
+    // Lines step around the hypotenuse, but floats go below the whole triangle.
+    let line = exclusions.place_line(Au(10), Au(10), Au(40)).unwrap();
+    assert_eq!(line.origin, Point::new(Au(50), Au(20)));
+    let size = Size::new(Au(10), Au(10));
+    assert_eq!(exclusions.place(Side::Right, &size).origin, Point::new(Au(90), Au(70)));
+
+    exclusions.set_inline_size(Au(200));
+    assert_eq!(exclusions.insets_at(Au(40)), (Au(30), Au(0)));
+}
+
+#[test]
+fn test_is_excluded() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(40), Au(10)));
+    for &(inline, block, excluded) in &[(0, 0, true), (29, 19, true), (-1, 0, false),
+                                         (0, -1, false), (30, 0, false), (29, 20, false),
+                                         (60, 0, true), (99, 9, true), (59, 0, false),
+                                         (60, 10, false), (100, 0, false), (50, 5, false),
+                                         (0, i32::MAX - 1, false)] {
+        assert_eq!(exclusions.is_excluded(&Point::new(Au(inline), Au(block))), excluded);
+    }
+}
+
+#[test]
+fn test_excluded_area() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.excluded_area(), 0);
+    assert_eq!(exclusions.bounds(), None);
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(20)));
+    assert_eq!(exclusions.excluded_area(), 30 * 20);
+    assert_eq!(exclusions.bounds(), Some((Au(0), Au(20))));
+
+    let mut exclusions = Exclusions::new(Au::from_px(10000));
+    exclusions.exclude(Side::Left, &Size::new(Au::from_px(10000), Au::from_px(10000)));
+    assert_eq!(exclusions.excluded_area(), 600_000 * 600_000);
+}
+
+#[test]
+fn test_height() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.height(), Au(0));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    exclusions.exclude(Side::Right, &Size::new(Au(50), Au(15)));
+    assert_eq!(exclusions.height(), Au(30));
+    exclusions.exclude(Side::Right, &Size::new(Au(5), Au(45)));
+    assert_eq!(exclusions.height(), Au(45));
+    assert_eq!(exclusions.height(),
+               cmp::max(exclusions.clearance(Side::Left), exclusions.clearance(Side::Right)));
+}
+
+#[test]
+fn test_columns() {
+    let mut columns = Columns::new(2, Au(100));
+    assert_eq!(columns.balance(), Some((0, Au(0))));
+    let size = Size::new(Au(60), Au(20));
+    for _ in 0..3 {
+        let placement = columns.place(1, Side::Left, &size);
+        let block_end = placement.origin.block + size.block;
+        columns.exclude(1, Side::Left, &Size::new(size.inline, block_end));
+    }
+    assert_eq!(columns.place(0, Side::Left, &size).origin, Point::new(Au(0), Au(0)));
+    assert_eq!(columns.column(0).band_count(), 1);
+    assert_eq!(columns.column(1).height(), Au(60));
+    assert_eq!(columns.height(), Au(60));
+    assert_eq!(columns.balance(), Some((1, Au(60))));
+    assert_eq!(Columns::new(0, Au(100)).height(), Au(0));
+}
+
+#[test]
+fn test_default() {
+    let mut exclusions = Exclusions::default();
+    assert_eq!(exclusions.band_count(), 1);
+    assert_eq!(exclusions.inline_size(), Au(0));
+    exclusions.set_inline_size(Au(100));
+    let placement = exclusions.place(Side::Left, &Size::new(Au(10), Au(10)));
+    assert_eq!(placement.available_inline_size, Au(100));
+}
+
+#[test]
+fn test_undo_last_exclude() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert!(!exclusions.undo_last_exclude());
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(30)));
+    assert_eq!(exclusions.band_count(), 3);
+    assert!(exclusions.undo_last_exclude());
+    assert_eq!(exclusions.band_count(), 2);
+    assert_eq!(exclusions.clearance(Side::Left), Au(10));
+    assert!(!exclusions.undo_last_exclude());
+
+    // Undoing restores the excluded areas that later rebuilds replay, too.
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(30)));
+    assert!(exclusions.undo_last_exclude());
+    exclusions.set_inline_size(Au(200));
+    assert_eq!(exclusions.insets_at(Au(0)), (Au(50), Au(0)));
+    assert!(!exclusions.undo_last_exclude());
+}
+
+#[test]
+fn test_outline() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.outline(Side::Left), vec![]);
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    assert_eq!(exclusions.outline(Side::Left),
+               vec![Point::new(Au(0), Au(0)), Point::new(Au(30), Au(0)),
+                    Point::new(Au(30), Au(20)), Point::new(Au(0), Au(20))]);
+
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(40)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(30)));
+    assert_eq!(exclusions.outline(Side::Left),
+               vec![Point::new(Au(0), Au(0)), Point::new(Au(30), Au(0)),
+                    Point::new(Au(30), Au(20)), Point::new(Au(10), Au(20)),
+                    Point::new(Au(10), Au(40)), Point::new(Au(0), Au(40))]);
+    assert_eq!(exclusions.outline(Side::Right),
+               vec![Point::new(Au(100), Au(0)), Point::new(Au(80), Au(0)),
+                    Point::new(Au(80), Au(30)), Point::new(Au(100), Au(30))]);
+    assert_eq!(Exclusions::new(Au(100)).outline(Side::Right), vec![]);
+}
+
+#[test]
+fn test_available_width() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(50), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    assert_eq!(exclusions.available_width(Au(0), Au(0)), Au(30));
+    assert_eq!(exclusions.available_width(Au(10), Au(5)), Au(40));
+    assert_eq!(exclusions.available_width(Au(5), Au(20)), Au(30));
+    assert_eq!(exclusions.available_width(Au(15), Au(10)), Au(40));
+    assert_eq!(exclusions.available_width(Au(20), Au(10)), Au(90));
+    assert_eq!(exclusions.available_width(Au(25), Au(100)), Au(90));
+    assert_eq!(exclusions.available_width(Au(30), Au(i32::MAX)), Au(100));
+}
+
+#[test]
+fn test_content_inline() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.min_content_inline(), Au(0));
+    assert_eq!(exclusions.max_content_inline(), Au(0));
+
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(40), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(40)));
+    assert_eq!(exclusions.min_content_inline(), Au(40));
+    assert_eq!(exclusions.max_content_inline(), Au(70));
+}
+
+#[test]
+fn test_px_conversions() {
+    assert_eq!(Point::from_px(0.0, 1.0), Point::new(Au(0), Au(60)));
+    assert_eq!(Size::from_px(12.5, -3.0), Size::new(Au(750), Au(-180)));
+    assert_eq!(Point::from_px(100.25, 0.5).to_px(), (100.25, 0.5));
+    assert_eq!(Size::from_px(1.0 / 3.0, 7.0).to_px(), (20.0 / 60.0, 7.0));
+
+    // Values finer than an app unit snap to the nearest one.
+    assert_eq!(Point::from_px(0.004, 0.01), Point::new(Au(0), Au(1)));
+    assert_eq!(Size::from_px(0.025, -0.024), Size::new(Au(2), Au(-1)));
+}
+
+#[test]
+fn test_widest_run_in() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(40)));
+    exclusions.exclude(Side::Right, &Size::new(Au(60), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(30)));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(50)));
+    assert_eq!(exclusions.widest_run_in(Au(0), Au(10)), (Au(10), Point::new(Au(30), Au(0))));
+    assert_eq!(exclusions.widest_run_in(Au(5), Au(30)), (Au(50), Point::new(Au(30), Au(10))));
+    assert_eq!(exclusions.widest_run_in(Au(5), Au(35)), (Au(70), Point::new(Au(30), Au(30))));
+    assert_eq!(exclusions.widest_run_in(Au(35), Au(45)), (Au(70), Point::new(Au(30), Au(35))));
+    assert_eq!(exclusions.widest_run_in(Au(0), Au(100)), (Au(100), Point::new(Au(0), Au(50))));
+    assert_eq!(exclusions.widest_run_in(Au(60), Au(70)), (Au(100), Point::new(Au(0), Au(60))));
+    assert_eq!(exclusions.widest_run_in(Au(20), Au(20)), (Au(100), Point::new(Au(0), Au(20))));
+    assert_eq!(exclusions.widest_run_in(Au(20), Au(10)), (Au(100), Point::new(Au(0), Au(20))));
+}
+
+#[test]
+fn test_nearest_boundary() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(40)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(50)));
+    let band_starts: Vec<_> = exclusions.bands().map(|band| band.0).collect();
+    assert_eq!(band_starts, vec![Au(0), Au(10), Au(40), Au(50)]);
+
+    assert_eq!(exclusions.nearest_boundary(Au(-20)), Au(0));
+    assert_eq!(exclusions.nearest_boundary(Au(0)), Au(0));
+    assert_eq!(exclusions.nearest_boundary(Au(4)), Au(0));
+    assert_eq!(exclusions.nearest_boundary(Au(5)), Au(0));
+    assert_eq!(exclusions.nearest_boundary(Au(6)), Au(10));
+    assert_eq!(exclusions.nearest_boundary(Au(24)), Au(10));
+    assert_eq!(exclusions.nearest_boundary(Au(25)), Au(10));
+    assert_eq!(exclusions.nearest_boundary(Au(26)), Au(40));
+    assert_eq!(exclusions.nearest_boundary(Au(40)), Au(40));
+    assert_eq!(exclusions.nearest_boundary(Au(46)), Au(50));
+    assert_eq!(exclusions.nearest_boundary(Au(1000)), Au(50));
+}
+
+#[test]
+fn test_band_count_in() {
+    let mut exclusions = Exclusions::new(Au(100));
+    for i in 0..10 {
+        exclusions.exclude(Side::Left, &Size::new(Au(10), Au(10 * (i + 1))));
+    }
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(120)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(60)));
+    assert_eq!(exclusions.band_count(), 4);
+    assert_eq!(exclusions.band_count_in(Au(0), Au(60)), 1);
+    assert_eq!(exclusions.band_count_in(Au(0), Au(100)), 2);
+    assert_eq!(exclusions.band_count_in(Au(50), Au(61)), 2);
+    assert_eq!(exclusions.band_count_in(Au(60), Au(100)), 1);
+    assert_eq!(exclusions.band_count_in(Au(99), Au(121)), 3);
+    assert_eq!(exclusions.band_count_in(Au(0), Au(i32::MAX)), 4);
+    assert_eq!(exclusions.band_count_in(Au(500), Au(600)), 1);
+    assert_eq!(exclusions.band_count_in(Au(50), Au(50)), 0);
+}
+
+#[test]
+fn test_map_retain_bands() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(40)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(60)));
+
+    // Record every app unit as its own entry, then keep only the ones where the insets change.
+    let mut map: SplayMap<Au, (Au, Au)> =
+        (0..100).map(|i| (Au(i), exclusions.insets_at(Au(i)))).collect();
+    let mut previous = None;
+    map.retain(|_, &mut insets| {
+        let changed = previous != Some(insets);
+        previous = Some(insets);
+        changed
+    });
+    let band_starts: Vec<_> = exclusions.bands().map(|band| band.0).collect();
+    assert_eq!(map.keys().cloned().collect::<Vec<_>>(), band_starts);
+    for i in 0..100 {
+        assert_eq!(*map.floor(&Au(i)).unwrap().1, exclusions.insets_at(Au(i)));
+    }
+}
+
+#[test]
+fn test_line_placements() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(25)));
+    exclusions.exclude(Side::Right, &Size::new(Au(60), Au(50)));
+    let lines: Vec<_> = exclusions.line_placements(Au(0), Au(10)).take(6).map(|placement| {
+        (placement.origin, placement.available_inline_size)
+    }).collect();
+    assert_eq!(lines,
+               vec![(Point::new(Au(30), Au(0)), Au(10)), (Point::new(Au(30), Au(10)), Au(10)),
+                    (Point::new(Au(30), Au(20)), Au(10)), (Point::new(Au(0), Au(30)), Au(40)),
+                    (Point::new(Au(0), Au(40)), Au(40)), (Point::new(Au(0), Au(50)), Au(100))]);
+
+    // Where the floats meet, the next line skips down past them.
+    exclusions.exclude(Side::Left, &Size::new(Au(40), Au(20)));
+    let lines: Vec<_> = exclusions.line_placements(Au(5), Au(10)).take(2).map(|placement| {
+        (placement.origin, placement.available_inline_size)
+    }).collect();
+    assert_eq!(lines,
+               vec![(Point::new(Au(30), Au(20)), Au(10)), (Point::new(Au(0), Au(30)), Au(40))]);
+}
+
+#[test]
+fn test_from_bands_rejects_invalid_bands() {
+    let max = i32::MAX;
+    let from_bands = |bands: &[(i32, i32, i32, i32)]| {
+        Exclusions::from_bands(Au(100), bands.iter().map(|&(block_start, length, left, right)| {
+            (Au(block_start), Au(length), Au(left), Au(right))
+        }).collect())
+    };
+    assert!(from_bands(&[(0, max, 0, 0)]).is_ok());
+    assert!(from_bands(&[(0, 10, 20, 5), (10, 10, 20, 0), (20, max - 20, 0, 0)]).is_ok());
+    assert_eq!(from_bands(&[(0, 10, 0, 0), (10, max - 10, 0, 0)]).unwrap().band_count(), 1);
+
+    assert_eq!(from_bands(&[]).unwrap_err(), InvalidBands);
+    assert!(from_bands(&[(0, 10, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 10, 0, 0), (20, max - 20, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 0, 0, 0), (0, max, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 10, 10, 0), (10, 10, 20, 0), (20, max - 20, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 10, 101, 0), (10, max - 10, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 10, 0, -1), (10, max - 10, 0, 0)]).is_err());
+    assert!(from_bands(&[(0, 10, 10, 0), (10, max - 10, 0, 5)]).is_err());
+}
+
+#[test]
+fn test_exclude_interval() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(20)));
+    exclusions.exclude_interval(Au(10), Au(30), (Au(40), Au(60)));
+    assert_eq!(exclusions.runs_at(Au(0)), vec![(Au(10), Au(100))]);
+    assert_eq!(exclusions.runs_at(Au(10)), vec![(Au(10), Au(40)), (Au(60), Au(100))]);
+    assert_eq!(exclusions.runs_at(Au(20)), vec![(Au(0), Au(40)), (Au(60), Au(100))]);
+    assert_eq!(exclusions.runs_at(Au(40)), vec![(Au(0), Au(100))]);
+    assert_eq!(exclusions.insets_at(Au(20)), (Au(0), Au(0)));
+
+    assert!(exclusions.is_excluded(&Point::new(Au(40), Au(10))));
+    assert!(!exclusions.is_excluded(&Point::new(Au(60), Au(10))));
+    assert!(!exclusions.is_excluded(&Point::new(Au(50), Au(40))));
+    assert!(exclusions.would_overlap(&Point::new(Au(20), Au(0)), &Size::new(Au(21), Au(11))));
+    assert!(!exclusions.would_overlap(&Point::new(Au(20), Au(0)), &Size::new(Au(20), Au(11))));
+
+    // Objects fit beside the rectangle if they can, and are pushed below it otherwise.
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(30), Au(20))).origin,
+               Point::new(Au(10), Au(0)));
+    assert_eq!(exclusions.place(Side::Right, &Size::new(Au(40), Au(20))).origin,
+               Point::new(Au(60), Au(0)));
+    assert_eq!(exclusions.place(Side::Left, &Size::new(Au(40), Au(20))).origin,
+               Point::new(Au(0), Au(40)));
+    assert_eq!(exclusions.place(Side::Right, &Size::new(Au(45), Au(5))).origin,
+               Point::new(Au(55), Au(0)));
+
+    exclusions.set_inline_size(Au(200));
+    assert_eq!(exclusions.runs_at(Au(10)), vec![(Au(10), Au(40)), (Au(60), Au(200))]);
+    exclusions.clear();
+    assert_eq!(exclusions.runs_at(Au(10)), vec![(Au(0), Au(200))]);
+}
+
+#[test]
+fn test_equality() {
+    let mut first = Exclusions::new(Au(100));
+    first.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    first.exclude(Side::Right, &Size::new(Au(30), Au(20)));
+    first.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    let mut second = Exclusions::new(Au(100));
+    second.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    second.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    second.exclude(Side::Right, &Size::new(Au(30), Au(20)));
+    assert_eq!(first, second);
+
+    second.exclude(Side::Right, &Size::new(Au(10), Au(5)));
+    assert_eq!(first, second);
+    second.exclude(Side::Right, &Size::new(Au(10), Au(25)));
+    assert!(first != second);
+    assert!(first != Exclusions::with_direction(Au(100), Direction::Rtl));
+}
+
+#[test]
+fn test_shrinking() {
+    thread_local!(static FAILURE: RefCell<Option<(InlineSize, Vec<Exclusion>)>> =
+                      const { RefCell::new(None) });
+
+    // A deliberately wrong property, since floats that don't fit side by side are stacked.
+    fn all_at_top(inline_size: InlineSize, exclusions: Vec<Exclusion>) -> bool {
+        let areas = place(inline_size, exclusions.clone());
+        let all_at_top = areas.iter().all(|area| area.origin.block == Au(0));
+        if !all_at_top {
+            FAILURE.with(|failure| *failure.borrow_mut() = Some((inline_size, exclusions)))
+        }
+        all_at_top
+    }
+
+    let property: fn(InlineSize, Vec<Exclusion>) -> bool = all_at_top;
+    assert!(QuickCheck::new().quicktest(property).is_err());
+    let (inline_size, exclusions) = FAILURE.with(|failure| failure.borrow_mut().take()).unwrap();
+    assert_eq!(inline_size, InlineSize(Au(1)));
+    let exclusions: Vec<_> =
+        exclusions.iter().map(|exclusion| (exclusion.side, exclusion.size)).collect();
+    assert_eq!(exclusions,
+               vec![(Side::Left, Size::new(Au(1), Au(1))), (Side::Left, Size::new(Au(1), Au(0)))]);
+}
+
+#[test]
+fn test_band_count() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.band_count(), 1);
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(20)));
+    assert_eq!(exclusions.band_count(), 2);
+    assert!(exclusions.approx_heap_bytes() > 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_rejects_invalid_bands() {
+    use self::tokens::Token;
+    let deserialize = |bands: &[(i32, i32, i32)]| -> Result<Exclusions, tokens::Error> {
+        let mut tokens = vec![Token::Seq(11), Token::I32(100), Token::Str("ltr".to_owned()),
+                              Token::Seq(bands.len())];
+        for &(block_position, length, left) in bands {
+            tokens.extend(vec![Token::Seq(2), Token::I32(block_position), Token::Seq(3),
+                               Token::I32(left), Token::I32(0), Token::I32(length)]);
+        }
+        tokens.extend(vec![Token::Seq(0), Token::Seq(0), Token::Seq(0), Token::I32(0),
+                           Token::Seq(0), Token::Seq(0), Token::Seq(0),
+                           Token::Str("horizontal-tb".to_owned())]);
+        Deserialize::deserialize(&mut tokens::Deserializer(tokens.into_iter()))
+    };
+    assert!(deserialize(&[(0, 10, 0), (10, i32::MAX - 10, 0)]).is_ok());
+    assert!(deserialize(&[(0, 10, 0), (20, i32::MAX - 20, 0)]).is_err());
+    assert!(deserialize(&[(0, 10, 0), (10, 10, 0)]).is_err());
+    assert!(deserialize(&[]).is_err());
+    assert!(deserialize(&[(0, 10, -100), (10, i32::MAX - 10, 0)]).is_ok());
+    assert!(deserialize(&[(0, 10, -101), (10, i32::MAX - 10, 0)]).is_err());
+    assert!(deserialize(&[(0, 10, 1), (10, i32::MAX - 10, 0)]).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_max_block() {
+    for &max_block in &[None, Some(Au(0)), Some(Au(50)), Some(Au(i32::MAX))] {
+        let mut exclusions = Exclusions::new(Au(100));
+        exclusions.set_max_block(max_block);
+        let mut serializer = tokens::Serializer(vec![]);
+        exclusions.serialize(&mut serializer).unwrap();
+        let mut deserializer = tokens::Deserializer(serializer.0.into_iter());
+        let round_tripped: Exclusions = Deserialize::deserialize(&mut deserializer).unwrap();
+        assert_eq!(round_tripped.max_block(), max_block);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_compact() {
+    use self::tokens::Token;
+    let bands = [(0, 10, -20, 0), (10, 10, -20, 0), (20, 5, -20, -30), (25, 5, 0, 0),
+                 (30, 10, 0, 0), (40, i32::MAX - 40, 0, 0)];
+    let mut tokens = vec![Token::Seq(11), Token::I32(100), Token::Str("ltr".to_owned()),
+                          Token::Seq(bands.len())];
+    for &(block_position, length, left, right) in &bands {
+        tokens.extend(vec![Token::Seq(2), Token::I32(block_position), Token::Seq(3),
+                           Token::I32(left), Token::I32(right), Token::I32(length)]);
+    }
+    tokens.extend(vec![Token::Seq(0), Token::Seq(0), Token::Seq(0), Token::I32(0),
+                       Token::Seq(0), Token::Seq(0), Token::Seq(0),
+                       Token::Str("horizontal-tb".to_owned())]);
+    let mut exclusions: Exclusions =
+        Deserialize::deserialize(&mut tokens::Deserializer(tokens.into_iter())).unwrap();
+    let insets: Vec<_> = (-5..60).map(|i| exclusions.insets_at(Au(i))).collect();
+
+    exclusions.compact();
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Au(20), Au(20), Au(0)), (Au(20), Au(5), Au(20), Au(30)),
+                    (Au(25), Au(i32::MAX - 25), Au(0), Au(0))]);
+    assert_eq!((-5..60).map(|i| exclusions.insets_at(Au(i))).collect::<Vec<_>>(), insets);
+
+    exclusions.compact();
+    assert_eq!(exclusions.band_count(), 3);
+}
+
+/// A minimal in-memory serialization format, used to test round-tripping without depending on a
+/// concrete format crate.
+#[cfg(feature = "serde")]
+mod tokens {
+    use serde::{de, ser};
+    use std::error;
+    use std::fmt::{self, Display, Formatter};
+    use std::vec;
+
+    #[derive(Clone, PartialEq, Debug)]
+    pub enum Token {
+        I32(i32),
+        Str(String),
+        /// The start of a sequence or tuple with the given number of elements.
+        Seq(usize),
+    }
+
+    #[derive(Debug)]
+    pub struct Error(String);
+
+    impl Display for Error {
+        fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+            formatter.write_str(&self.0)
+        }
+    }
+
+    impl error::Error for Error {
+        fn description(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl ser::Error for Error {
+        fn custom<T: Into<String>>(message: T) -> Error {
+            Error(message.into())
+        }
+    }
+
+    impl de::Error for Error {
+        fn custom<T: Into<String>>(message: T) -> Error {
+            Error(message.into())
+        }
+
+        fn end_of_stream() -> Error {
+            Error("end of stream".to_owned())
+        }
+    }
+
+    fn unsupported() -> Result<(), Error> {
+        Err(Error("unsupported".to_owned()))
+    }
+
+    pub struct Serializer(pub Vec<Token>);
+
+    pub struct Deserializer(pub vec::IntoIter<Token>);
+
+    impl ser::Serializer for Serializer {
+        type Error = Error;
+        type SeqState = usize;
+        type TupleState = usize;
+        type TupleStructState = ();
+        type TupleVariantState = ();
+        type MapState = ();
+        type StructState = ();
+        type StructVariantState = ();
+
+        fn serialize_i32(&mut self, value: i32) -> Result<(), Error> {
+            self.0.push(Token::I32(value));
+            Ok(())
+        }
+
+        fn serialize_str(&mut self, value: &str) -> Result<(), Error> {
+            self.0.push(Token::Str(value.to_owned()));
+            Ok(())
+        }
+
+        fn serialize_seq(&mut self, _: Option<usize>) -> Result<usize, Error> {
+            self.0.push(Token::Seq(0));
+            Ok(self.0.len() - 1)
+        }
+
+        fn serialize_seq_elt<T: ser::Serialize>(&mut self, start: &mut usize, value: T)
+                                                -> Result<(), Error> {
+            if let Token::Seq(ref mut length) = self.0[*start] {
+                *length += 1
+            }
+            value.serialize(self)
+        }
+
+        fn serialize_seq_end(&mut self, _: usize) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn serialize_seq_fixed_size(&mut self, size: usize) -> Result<usize, Error> {
+            self.serialize_tuple(size)
+        }
+
+        fn serialize_tuple(&mut self, size: usize) -> Result<usize, Error> {
+            self.0.push(Token::Seq(size));
+            Ok(self.0.len() - 1)
+        }
+
+        fn serialize_tuple_elt<T: ser::Serialize>(&mut self, _: &mut usize, value: T)
+                                                  -> Result<(), Error> {
+            value.serialize(self)
+        }
+
+        fn serialize_tuple_end(&mut self, _: usize) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn serialize_bool(&mut self, _: bool) -> Result<(), Error> { unsupported() }
+        fn serialize_isize(&mut self, _: isize) -> Result<(), Error> { unsupported() }
+        fn serialize_i8(&mut self, _: i8) -> Result<(), Error> { unsupported() }
+        fn serialize_i16(&mut self, _: i16) -> Result<(), Error> { unsupported() }
+        fn serialize_i64(&mut self, _: i64) -> Result<(), Error> { unsupported() }
+        fn serialize_usize(&mut self, _: usize) -> Result<(), Error> { unsupported() }
+        fn serialize_u8(&mut self, _: u8) -> Result<(), Error> { unsupported() }
+        fn serialize_u16(&mut self, _: u16) -> Result<(), Error> { unsupported() }
+        fn serialize_u32(&mut self, _: u32) -> Result<(), Error> { unsupported() }
+        fn serialize_u64(&mut self, _: u64) -> Result<(), Error> { unsupported() }
+        fn serialize_f32(&mut self, _: f32) -> Result<(), Error> { unsupported() }
+        fn serialize_f64(&mut self, _: f64) -> Result<(), Error> { unsupported() }
+        fn serialize_char(&mut self, _: char) -> Result<(), Error> { unsupported() }
+        fn serialize_bytes(&mut self, _: &[u8]) -> Result<(), Error> { unsupported() }
+        fn serialize_unit(&mut self) -> Result<(), Error> { unsupported() }
+        fn serialize_unit_struct(&mut self, _: &'static str) -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_unit_variant(&mut self, _: &'static str, _: usize, _: &'static str)
+                                  -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_newtype_struct<T: ser::Serialize>(&mut self, _: &'static str, _: T)
+                                                       -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_newtype_variant<T: ser::Serialize>(&mut self,
+                                                        _: &'static str,
+                                                        _: usize,
+                                                        _: &'static str,
+                                                        _: T)
+                                                        -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_none(&mut self) -> Result<(), Error> { unsupported() }
+        fn serialize_some<T: ser::Serialize>(&mut self, _: T) -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_tuple_struct(&mut self, _: &'static str, _: usize) -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_tuple_struct_elt<T: ser::Serialize>(&mut self, _: &mut (), _: T)
+                                                         -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_tuple_struct_end(&mut self, _: ()) -> Result<(), Error> { unsupported() }
+        fn serialize_tuple_variant(&mut self,
+                                   _: &'static str,
+                                   _: usize,
+                                   _: &'static str,
+                                   _: usize)
+                                   -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_tuple_variant_elt<T: ser::Serialize>(&mut self, _: &mut (), _: T)
+                                                          -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_tuple_variant_end(&mut self, _: ()) -> Result<(), Error> { unsupported() }
+        fn serialize_map(&mut self, _: Option<usize>) -> Result<(), Error> { unsupported() }
+        fn serialize_map_key<T: ser::Serialize>(&mut self, _: &mut (), _: T)
+                                                -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_map_value<T: ser::Serialize>(&mut self, _: &mut (), _: T)
+                                                  -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_map_end(&mut self, _: ()) -> Result<(), Error> { unsupported() }
+        fn serialize_struct(&mut self, _: &'static str, _: usize) -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_struct_elt<V: ser::Serialize>(&mut self, _: &mut (), _: &'static str, _: V)
+                                                   -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_struct_end(&mut self, _: ()) -> Result<(), Error> { unsupported() }
+        fn serialize_struct_variant(&mut self,
+                                    _: &'static str,
+                                    _: usize,
+                                    _: &'static str,
+                                    _: usize)
+                                    -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_struct_variant_elt<V: ser::Serialize>(&mut self,
+                                                           _: &mut (),
+                                                           _: &'static str,
+                                                           _: V)
+                                                           -> Result<(), Error> {
+            unsupported()
+        }
+        fn serialize_struct_variant_end(&mut self, _: ()) -> Result<(), Error> {
+            unsupported()
+        }
+    }
+
+    struct SeqVisitor<'a> {
+        tokens: &'a mut Deserializer,
+        remaining: usize,
+    }
+
+    impl<'a> de::SeqVisitor for SeqVisitor<'a> {
+        type Error = Error;
+
+        fn visit<T: de::Deserialize>(&mut self) -> Result<Option<T>, Error> {
+            if self.remaining == 0 {
+                return Ok(None)
+            }
+            self.remaining -= 1;
+            de::Deserialize::deserialize(self.tokens).map(Some)
+        }
+
+        fn end(&mut self) -> Result<(), Error> {
+            if self.remaining == 0 {
+                Ok(())
+            } else {
+                Err(de::Error::invalid_length(self.remaining))
+            }
+        }
+    }
+
+    impl de::Deserializer for Deserializer {
+        type Error = Error;
+
+        fn deserialize<V: de::Visitor>(&mut self, mut visitor: V) -> Result<V::Value, Error> {
+            match self.0.next() {
+                Some(Token::I32(value)) => visitor.visit_i32(value),
+                Some(Token::Str(value)) => visitor.visit_string(value),
+                Some(Token::Seq(length)) => {
+                    visitor.visit_seq(SeqVisitor {
+                        tokens: self,
+                        remaining: length,
+                    })
+                }
+                None => Err(de::Error::end_of_stream()),
+            }
+        }
+
+        forward_to_deserialize! {
+            bool usize u8 u16 u32 u64 isize i8 i16 i32 i64 f32 f64 char str string unit option
+            seq seq_fixed_size bytes map unit_struct newtype_struct tuple_struct struct
+            struct_field tuple enum ignored_any
+        }
+    }
+}
+
+#[test]
+fn test_place_line() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+
+    // A short line fits next to the first float.
+    let placement = exclusions.place_line(Au(0), Au(10), Au(20)).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(60), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(20));
+
+    // A tall, wide line must skip down past the widest float, then span two bands.
+    let placement = exclusions.place_line(Au(0), Au(15), Au(50)).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(10), Au(10)));
+    assert_eq!(placement.available_inline_size, Au(70));
+
+    // Lines are never placed above the given block position.
+    let placement = exclusions.place_line(Au(25), Au(10), Au(50)).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(10), Au(25)));
+    assert_eq!(placement.available_inline_size, Au(90));
+
+    assert!(exclusions.place_line(Au(0), Au(10), Au(101)).is_none());
+
+    // Rectangles excluded with `exclude_interval` don't affect the line box.
+    exclusions.exclude_interval(Au(25), Au(10), (Au(40), Au(60)));
+    let placement = exclusions.place_line(Au(25), Au(10), Au(50)).unwrap();
+    assert_eq!(placement.origin, Point::new(Au(10), Au(25)));
+    assert_eq!(placement.available_inline_size, Au(90));
+    assert_eq!(exclusions.runs_at(Au(25)), vec![(Au(10), Au(40)), (Au(60), Au(100))]);
+}
+
+#[test]
+fn test_available_run() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(80), Au(5)));
+
+    let (inline_start, inline_end) = exclusions.available_run(Au(5));
+    assert_eq!((inline_start, inline_end), (Au(30), Au(80)));
+    let size = Size::new(inline_end - inline_start, Au(1));
+    assert_eq!(exclusions.place_below(Side::Left, &size, Au(5)).available_inline_size,
+               inline_end - inline_start);
+
+    assert_eq!(exclusions.available_run(Au(15)), (Au(30), Au(100)));
+    assert_eq!(exclusions.available_run(Au(0)), (Au(30), Au(30)));
+}
+
+#[test]
+fn test_map_len() {
+    let mut map = SplayMap::new();
+    assert!(map.is_empty());
+    for key in &[5, 3, 8, 1, 4] {
+        assert_eq!(map.insert(*key, *key * 10), None);
+    }
+    assert_eq!(map.len(), 5);
+    assert_eq!(map.insert(3, 31), Some(30));
+    assert_eq!(map.len(), 5);
+    assert_eq!(map.remove(&8), Some(80));
+    assert_eq!(map.remove(&8), None);
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.insert(8, 81), None);
+    assert_eq!(map.len(), 5);
+    for key in &[1, 3, 4, 5, 8] {
+        map.remove(key);
+    }
+    assert_eq!(map.len(), 0);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_map_remove() {
+    let values = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+    let mut map: SplayMap<i32, &str> = (0..10).zip(values.iter().cloned()).collect();
+    assert_eq!(map.remove(&4), Some("e"));
+    assert_eq!(map.remove(&4), None);
+    assert_eq!(map.remove(&42), None);
+    assert_eq!(map.remove(&0), Some("a"));
+    assert_eq!(map.remove(&9), Some("j"));
+    assert_eq!(map.get(&5), Some(&"f"));
+    assert_eq!(map.len(), 7);
+}
+
+#[test]
+fn test_map_entry() {
+    let (mut with_entry, mut without_entry) = (SplayMap::new(), SplayMap::new());
+    for &(key, value) in &[(5, 1), (2, 2), (5, 3), (9, 4), (2, 5), (7, 6)] {
+        *with_entry.entry(key).and_modify(|old| *old += value).or_insert(value) *= 2;
+
+        let new_value = match without_entry.get(&key) {
+            Some(&old) => old + value,
+            None => value,
+        };
+        without_entry.insert(key, new_value * 2);
+    }
+    assert_eq!(with_entry.len(), without_entry.len());
+    for key in 0..10 {
+        assert_eq!(with_entry.get(&key), without_entry.get(&key));
+    }
+}
+
+#[test]
+fn test_map_upper_bound_with() {
+    fn at_most(limit: i32, value: i32) -> Ordering {
+        if value <= limit { Ordering::Greater } else { Ordering::Less }
+    }
+
+    let map: SplayMap<i32, i32> = (0..20).map(|key| (key * 5, key)).collect();
+    assert_eq!(map.upper_bound_with(|&key, _| at_most(42, key)), Some(&(40, 8)));
+    assert_eq!(map.upper_bound_with(|&key, _| at_most(40, key)), Some(&(40, 8)));
+    assert_eq!(map.upper_bound_with(|_, &value| at_most(3, value)), Some(&(15, 3)));
+    assert_eq!(map.upper_bound_with(|&key, _| at_most(-1, key)), None);
+    assert_eq!(map.upper_bound_with(|&key, _| at_most(1000, key)), Some(&(95, 19)));
+    assert_eq!(map.upper_bound_with(|&key, _| 40.cmp(&key)), Some(&(40, 8)));
+}
+
+#[test]
+fn test_map_get_or_insert_with() {
+    let mut map: SplayMap<i32, &str> = (0..10).map(|key| (key * 2, "even")).collect();
+    let mut calls = 0;
+    *map.get_or_insert_with(4, || { calls += 1; "new" }) = "four";
+    assert_eq!(calls, 0);
+    assert_eq!(*map.get_or_insert_with(5, || { calls += 1; "five" }), "five");
+    assert_eq!(calls, 1);
+    assert_eq!(*map.get_or_insert_with(5, || { calls += 1; "new" }), "five");
+    assert_eq!(calls, 1);
+    assert_eq!(map.len(), 11);
+    assert_eq!(map.get(&4), Some(&"four"));
+    assert_eq!(map.keys().cloned().collect::<Vec<_>>(),
+               vec![0, 2, 4, 5, 6, 8, 10, 12, 14, 16, 18]);
+
+    let mut empty = SplayMap::new();
+    assert_eq!(*empty.get_or_insert_with(1, || 2), 2);
+    assert_eq!(empty.len(), 1);
+}
+
+/// Returns a map from each key in `0..n` to itself.
+///
+/// Inserting keys in ascending order leaves the tree as a single left spine.
+fn ascending_map(n: i32) -> SplayMap<i32, i32> {
+    (0..n).map(|key| (key, key)).collect()
+}
+
+#[test]
+fn test_map_rebalance() {
+    let mut map = ascending_map(1000);
+    assert_eq!(map.depth(), 1000);
+    map.rebalance();
+    assert_eq!(map.depth(), 10);
+    assert_eq!(map.len(), 1000);
+    assert!(map.iter().map(|(&key, &value)| (key, value)).eq((0..1000).map(|key| (key, key))));
+    assert_eq!(map.get(&999), Some(&999));
+
+    for count in 0..20 {
+        let mut map: SplayMap<i32, i32> = (0..count).map(|key| (key, key)).collect();
+        map.rebalance();
+        assert_eq!(map.depth(), (32 - (count as u32).leading_zeros()) as usize);
+        assert_eq!(map.keys().cloned().collect::<Vec<_>>(), (0..count).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_map_depth_stats() {
+    assert_eq!(SplayMap::<i32, i32>::new().depth_stats(), (0, 0.0));
+
+    let mut map = ascending_map(1000);
+    assert_eq!(map.depth_stats(), (1000, 500.5));
+    map.rebalance();
+    let (max_depth, average_depth) = map.depth_stats();
+    assert_eq!(max_depth, 10);
+    assert!(average_depth > 8.0 && average_depth < 9.0);
+}
+
+#[test]
+fn test_map_get_with() {
+    fn within(lo: i32, hi: i32, key: i32) -> Ordering {
+        if key >= hi {
+            Ordering::Less
+        } else if key < lo {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    let mut map: SplayMap<i32, i32> = (0..50).map(|key| (key * 3, key)).collect();
+    for lo in -5..155 {
+        let hash = map.structure_hash();
+        let found = map.get_with(|&key, _| within(lo, lo + 3, key))
+                       .map(|(&key, &value)| (key, value));
+        assert_eq!(map.structure_hash(), hash);
+        assert_eq!(found, map.get_with_mut(|&key, _| within(lo, lo + 3, key)).cloned());
+    }
+    assert_eq!(map.get_with(|_, &value| 20.cmp(&value)), Some((&60, &20)));
+    assert_eq!(map.get_with(|&key, _| within(151, 200, key)), None);
+    assert_eq!(SplayMap::<i32, i32>::new().get_with(|_, _| Ordering::Equal), None);
+}
+
+#[test]
+fn test_map_clear_to() {
+    let mut map: SplayMap<i32, i32> = SplayMap::new();
+    map.clear_to(5, 50);
+    assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&5, &50)]);
+
+    // Inserting a key splays it to the root, whose node is then reused.
+    map.extend((0..100).map(|key| (key, -key)));
+    let root = map.get(&99).unwrap() as *const i32;
+    map.clear_to(7, 70);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&7, &70)]);
+    assert_eq!(map.get(&7).unwrap() as *const i32, root);
+}
+
+#[test]
+fn test_map_get_mut() {
+    let mut map: SplayMap<Au, (Au, Au)> = (0..10).map(|i| (Au(i * 10), (Au(0), Au(0)))).collect();
+    map.get_mut(&Au(30)).unwrap().0 = Au(-15);
+    assert!(map.get_mut(&Au(35)).is_none());
+    map[&Au(90)].1 = Au(-5);
+    assert_eq!(map.get(&Au(30)), Some(&(Au(-15), Au(0))));
+    assert_eq!(map.get(&Au(90)), Some(&(Au(0), Au(-5))));
+    assert_eq!(map.get(&Au(40)), Some(&(Au(0), Au(0))));
+}
+
+#[test]
+fn test_map_drop_deep_tree() {
+    drop(ascending_map(200_000));
+
+    let mut iter = ascending_map(200_000).into_iter();
+    assert_eq!(iter.next_back(), Some((199_999, 199_999)));
+    drop(iter);
+}
@@ -0,0 +1,32 @@
+// Any copyright is dedicated to the Public Domain.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Smoke tests for builds without the `std` feature. Run with
+//! `cargo test --no-default-features`.
+
+use app_units::Au;
+use columns::Columns;
+use exclusions::{Exclusions, Point, Side, Size};
+
+#[test]
+fn test_place_and_exclude() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(10)));
+
+    let placement = exclusions.place(Side::Left, &Size::new(Au(20), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(50), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(20));
+    let placement = exclusions.place(Side::Right, &Size::new(Au(40), Au(10)));
+    assert_eq!(placement.origin, Point::new(Au(60), Au(10)));
+    assert_eq!(exclusions.bands().count(), 3);
+}
+
+#[test]
+fn test_columns() {
+    let mut columns = Columns::new(2, Au(100));
+    columns.exclude(1, Side::Left, &Size::new(Au(60), Au(10)));
+    let size = Size::new(Au(50), Au(10));
+    assert_eq!(columns.place(0, Side::Left, &size).origin, Point::new(Au(0), Au(0)));
+    assert_eq!(columns.place(1, Side::Left, &size).origin, Point::new(Au(0), Au(10)));
+}